@@ -0,0 +1,67 @@
+//! How long monsters take to find their way to the player, on a full size map.
+//!
+//! Run with `cargo bench --no-default-features`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rand::{Rng, SeedableRng};
+
+use roguelike::colors::DESATURATED_GREEN;
+use roguelike::game::{new_game, Game, GameRng};
+use roguelike::map::{is_blocked, MAP_HEIGHT, MAP_WIDTH};
+use roguelike::object::Object;
+use roguelike::path::find_path;
+use roguelike::spawns::SpawnTable;
+use roguelike::world::{Bundle, World};
+
+/// how many monsters chase the player
+const NUM_MONSTERS: usize = 40;
+
+/// a generated `MAP_WIDTH` x `MAP_HEIGHT` dungeon, with `NUM_MONSTERS` orcs
+/// scattered over it
+fn crowded_level() -> (Game, World) {
+    let (game, mut world) = new_game(1234, SpawnTable::default());
+    let mut rng = GameRng::seed_from_u64(1234);
+    let mut placed = world.ais.entities().count();
+    while placed < NUM_MONSTERS {
+        let x = rng.gen_range(0..MAP_WIDTH);
+        let y = rng.gen_range(0..MAP_HEIGHT);
+        if !is_blocked(x, y, &game.map, &world.objects) {
+            let mut orc = Object::new(x, y, 'o', DESATURATED_GREEN, true, "orc");
+            orc.alive = true;
+            world.spawn(Bundle::new(orc));
+            placed += 1;
+        }
+    }
+    (game, world)
+}
+
+fn pathfinding(c: &mut Criterion) {
+    let (game, world) = crowded_level();
+    let objects = &world.objects;
+    let player = objects[world.player].pos();
+    let monsters: Vec<_> = objects
+        .values()
+        .filter(|o| o.blocks && o.pos() != player)
+        .map(|o| o.pos())
+        .collect();
+
+    c.bench_function("every monster finds a path to the player", |b| {
+        b.iter(|| {
+            for &monster in &monsters {
+                black_box(find_path(&game.map, objects, monster, player));
+            }
+        })
+    });
+
+    // the longest search: from whichever monster is furthest away
+    let furthest = *monsters
+        .iter()
+        .max_by_key(|&&(x, y)| (x - player.0).pow(2) + (y - player.1).pow(2))
+        .unwrap();
+    c.bench_function("the furthest monster finds a path to the player", |b| {
+        b.iter(|| black_box(find_path(&game.map, objects, furthest, player)))
+    });
+}
+
+criterion_group!(benches, pathfinding);
+criterion_main!(benches);
@@ -0,0 +1,278 @@
+//! The game in a terminal, drawn with truecolor ANSI escapes, so it can be
+//! played over SSH. Needs a terminal of at least 80x50.
+
+use std::io::{self, BufWriter, Stdout, Write};
+use std::time::Duration;
+
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, KeyCode, KeyEvent, KeyEventKind, KeyEventState,
+    KeyModifiers, KeyboardEnhancementFlags, MouseButton, MouseEventKind,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::style::{self, Print, SetBackgroundColor, SetForegroundColor};
+use crossterm::{cursor, execute, queue, terminal};
+
+use roguelike::buffer::Buffer;
+use roguelike::colors::Color;
+use roguelike::console::{Console, Event, Input, Mouse};
+use roguelike::keys::Keypress;
+use roguelike::ui::{self, Ui, LIMIT_FPS, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+/*********  STRUCTURES  *********/
+/// The game in the terminal it was started from
+struct Terminal {
+    out: BufWriter<Stdout>,
+    /// what has been drawn since the last flush
+    cells: Buffer,
+    /// what the terminal is showing, so a flush only sends the cells that
+    /// changed; `None` if it isn't known
+    shown: Option<Buffer>,
+    /// whether the terminal tells number pad keys apart from the others
+    keyboard_enhanced: bool,
+    /// whether the player pressed Ctrl+C
+    closed: bool,
+}
+
+fn main() {
+    let setup = ui::setup(std::env::args().skip(1));
+
+    match terminal::size() {
+        Ok((width, height)) if (width as i32) < SCREEN_WIDTH || (height as i32) < SCREEN_HEIGHT => {
+            eprintln!(
+                "The terminal is {}x{}, but the game needs at least {}x{}.",
+                width, height, SCREEN_WIDTH, SCREEN_HEIGHT
+            );
+            std::process::exit(1);
+        }
+        Ok(_) => {}
+        Err(err) => {
+            eprintln!("Could not use the terminal: {}", err);
+            std::process::exit(1);
+        }
+    }
+
+    // put the terminal back before a panic message is printed, so it can be read
+    let hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore(&mut io::stdout(), false);
+        hook(info);
+    }));
+
+    let terminal = match Terminal::new() {
+        Ok(terminal) => terminal,
+        Err(err) => {
+            let _ = restore(&mut io::stdout(), false);
+            eprintln!("Could not set up the terminal: {}", err);
+            std::process::exit(1);
+        }
+    };
+    let mut ui = Ui::new(terminal, setup.keys);
+    ui::main_menu(&mut ui, setup.seed, &setup.spawns);
+}
+
+impl Terminal {
+    fn new() -> io::Result<Self> {
+        let mut out = BufWriter::new(io::stdout());
+        terminal::enable_raw_mode()?;
+        execute!(
+            out,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            EnableMouseCapture,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        // without this the number pad just types digits
+        let keyboard_enhanced = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if keyboard_enhanced {
+            execute!(
+                out,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+            )?;
+        }
+        Ok(Terminal {
+            out,
+            cells: Buffer::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+            shown: None,
+            keyboard_enhanced,
+            closed: false,
+        })
+    }
+
+    /// send the cells that changed to the terminal
+    fn draw(&mut self) -> io::Result<()> {
+        let changed: Vec<_> = match &self.shown {
+            Some(shown) => shown
+                .diff(&self.cells)
+                .into_iter()
+                .map(|change| (change.x, change.y, change.after))
+                .collect(),
+            None => self.cells.cells().collect(),
+        };
+        // the colors the terminal is set to, so they are only sent when they change
+        let mut colors = None;
+        // where the terminal's cursor is, so it only has to be moved after a gap
+        let mut cursor = None;
+        for (x, y, cell) in changed {
+            if cursor != Some((x, y)) {
+                queue!(self.out, cursor::MoveTo(x as u16, y as u16))?;
+            }
+            if colors != Some((cell.foreground, cell.background)) {
+                queue!(
+                    self.out,
+                    SetForegroundColor(ansi_color(cell.foreground)),
+                    SetBackgroundColor(ansi_color(cell.background))
+                )?;
+                colors = Some((cell.foreground, cell.background));
+            }
+            queue!(self.out, Print(cell.glyph))?;
+            cursor = Some((x + 1, y));
+        }
+        self.shown = Some(self.cells.clone());
+        self.out.flush()
+    }
+
+    /// translate a crossterm event into one the game knows about
+    fn event(&mut self, event: event::Event) -> Option<Event> {
+        match event {
+            event::Event::Key(key) if key.kind != KeyEventKind::Release => {
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                    self.closed = true;
+                    return None;
+                }
+                keypress(key).map(Event::Key)
+            }
+            event::Event::Mouse(mouse) => {
+                let (left, right) = match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => (true, false),
+                    MouseEventKind::Down(MouseButton::Right) => (false, true),
+                    MouseEventKind::Moved | MouseEventKind::Drag(_) => (false, false),
+                    _ => return None,
+                };
+                Some(Event::Mouse(Mouse {
+                    x: mouse.column as i32,
+                    y: mouse.row as i32,
+                    left,
+                    right,
+                }))
+            }
+            event::Event::Resize(_, _) => {
+                // whatever was on screen may be gone, so draw it all again
+                self.shown = None;
+                let _ = execute!(self.out, terminal::Clear(terminal::ClearType::All));
+                let _ = self.draw();
+                None
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Console for Terminal {
+    fn size(&self) -> (i32, i32) {
+        self.cells.size()
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: Color) {
+        self.cells.put_char(x, y, glyph, color);
+    }
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color) {
+        self.cells.set_background(x, y, color);
+    }
+
+    fn background(&self, x: i32, y: i32) -> Color {
+        self.cells.background(x, y)
+    }
+
+    fn flush(&mut self) {
+        // there's nowhere to report this that the player would see; if the
+        // terminal is gone, they will find out soon enough
+        let _ = self.draw();
+    }
+}
+
+impl Input for Terminal {
+    fn check_for_event(&mut self) -> Option<Event> {
+        // wait up to a frame, which keeps the game at its frame rate
+        let frame = Duration::from_millis(1000 / LIMIT_FPS as u64);
+        match event::poll(frame) {
+            Ok(true) => event::read().ok().and_then(|event| self.event(event)),
+            _ => None,
+        }
+    }
+
+    fn wait_for_keypress(&mut self) -> Option<Keypress> {
+        while !self.closed {
+            match event::read() {
+                Ok(event) => {
+                    if let Some(Event::Key(key)) = self.event(event) {
+                        return Some(key);
+                    }
+                }
+                Err(_) => self.closed = true,
+            }
+        }
+        None
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed
+    }
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = self.out.flush();
+        let _ = restore(self.out.get_mut(), self.keyboard_enhanced);
+    }
+}
+
+/// put the terminal back the way it was before the game started
+fn restore(out: &mut impl Write, keyboard_enhanced: bool) -> io::Result<()> {
+    if keyboard_enhanced {
+        execute!(out, PopKeyboardEnhancementFlags)?;
+    }
+    execute!(
+        out,
+        style::ResetColor,
+        DisableMouseCapture,
+        cursor::Show,
+        terminal::LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()
+}
+
+/// convert a color from the game core into a truecolor terminal one
+fn ansi_color(color: Color) -> style::Color {
+    style::Color::Rgb {
+        r: color.r,
+        g: color.g,
+        b: color.b,
+    }
+}
+
+/// translate a crossterm key into the key the bindings know about
+fn keypress(key: KeyEvent) -> Option<Keypress> {
+    let keypress = match key.code {
+        KeyCode::Char(c) if key.state.contains(KeyEventState::KEYPAD) && c.is_ascii_digit() => {
+            Keypress::NumPad(c as u8 - b'0')
+        }
+        KeyCode::Char(c) => Keypress::Char(c),
+        KeyCode::Up => Keypress::Up,
+        KeyCode::Down => Keypress::Down,
+        KeyCode::Left => Keypress::Left,
+        KeyCode::Right => Keypress::Right,
+        KeyCode::Home => Keypress::Home,
+        KeyCode::End => Keypress::End,
+        KeyCode::PageUp => Keypress::PageUp,
+        KeyCode::PageDown => Keypress::PageDown,
+        KeyCode::Esc => Keypress::Escape,
+        KeyCode::Enter => Keypress::Enter,
+        _ => return None,
+    };
+    Some(keypress)
+}
@@ -0,0 +1,202 @@
+//! A console that only exists in memory, so what the screens draw can be
+//! looked at without a window, and compared from one frame to the next.
+
+use std::fmt;
+
+use crate::colors::{self, Color};
+use crate::console::Console;
+
+/*********  CONSTANTS  **********/
+/// the letters that stand for colors in `foregrounds_text` and `backgrounds_text`
+const COLOR_KEYS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/*********  STRUCTURES  *********/
+/// What is drawn in one character cell
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    pub foreground: Color,
+    pub background: Color,
+}
+
+/// A grid of cells, drawn on like any other console
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Buffer {
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+}
+
+/// A cell that is different in one frame from another
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CellChange {
+    pub x: i32,
+    pub y: i32,
+    pub before: Cell,
+    pub after: Cell,
+}
+
+impl Cell {
+    /// what a cell is after a clear
+    pub const BLANK: Cell = Cell {
+        glyph: ' ',
+        foreground: colors::WHITE,
+        background: colors::BLACK,
+    };
+}
+
+impl Buffer {
+    /// a buffer of blank cells
+    pub fn new(width: i32, height: i32) -> Self {
+        Buffer {
+            width,
+            height,
+            cells: vec![Cell::BLANK; (width * height).max(0) as usize],
+        }
+    }
+
+    /// the cell at `(x, y)`, if that is inside the buffer
+    pub fn cell(&self, x: i32, y: i32) -> Option<Cell> {
+        self.index(x, y).map(|index| self.cells[index])
+    }
+
+    /// every cell, with its position, a row at a time from the top left
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32, Cell)> + '_ {
+        self.cells.iter().enumerate().map(move |(index, &cell)| {
+            let index = index as i32;
+            (index % self.width, index / self.width, cell)
+        })
+    }
+
+    /// The glyphs, a line of text for each row. Spaces at the end of a row are
+    /// left out.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for row in self.cells.chunks(self.width.max(1) as usize) {
+            let line: String = row.iter().map(|cell| cell.glyph).collect();
+            text.push_str(line.trim_end());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// The foreground colors as text: which letter stands for which color,
+    /// then a line of letters for each row
+    pub fn foregrounds_text(&self) -> String {
+        self.colors_text(|cell| cell.foreground)
+    }
+
+    /// the background colors, like `foregrounds_text`
+    pub fn backgrounds_text(&self) -> String {
+        self.colors_text(|cell| cell.background)
+    }
+
+    /// Every cell that is different in `other`, a row at a time from the top
+    /// left. A buffer of a different size is different everywhere the two
+    /// overlap, and nowhere else.
+    pub fn diff(&self, other: &Buffer) -> Vec<CellChange> {
+        self.cells()
+            .filter_map(|(x, y, before)| {
+                let after = other.cell(x, y)?;
+                if before != after {
+                    Some(CellChange {
+                        x,
+                        y,
+                        before,
+                        after,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// one color of every cell as text, with a letter for each color in the
+    /// order they first turn up
+    fn colors_text(&self, color: impl Fn(&Cell) -> Color) -> String {
+        let mut seen: Vec<Color> = vec![];
+        let mut rows = String::new();
+        for row in self.cells.chunks(self.width.max(1) as usize) {
+            for cell in row {
+                let color = color(cell);
+                let index = match seen.iter().position(|&c| c == color) {
+                    Some(index) => index,
+                    None => {
+                        seen.push(color);
+                        seen.len() - 1
+                    }
+                };
+                // past the last letter, they all look alike
+                rows.push(COLOR_KEYS.chars().nth(index).unwrap_or('?'));
+            }
+            rows.push('\n');
+        }
+        let mut text: String = COLOR_KEYS
+            .chars()
+            .zip(&seen)
+            .map(|(key, color)| format!("{} = {}\n", key, color))
+            .collect();
+        text.push_str(&rows);
+        text
+    }
+}
+
+impl Console for Buffer {
+    fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|cell| *cell = Cell::BLANK);
+    }
+
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: Color) {
+        if let Some(index) = self.index(x, y) {
+            self.cells[index].glyph = glyph;
+            self.cells[index].foreground = color;
+        }
+    }
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color) {
+        if let Some(index) = self.index(x, y) {
+            self.cells[index].background = color;
+        }
+    }
+
+    fn background(&self, x: i32, y: i32) -> Color {
+        self.cell(x, y)
+            .map_or(colors::BLACK, |cell| cell.background)
+    }
+
+    /// nothing to show it on; it's all in the cells already
+    fn flush(&mut self) {}
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} {} on {}",
+            self.glyph, self.foreground, self.background
+        )
+    }
+}
+
+impl fmt::Display for CellChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "({}, {}): {} -> {}",
+            self.x, self.y, self.before, self.after
+        )
+    }
+}
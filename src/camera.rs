@@ -0,0 +1,56 @@
+/// The part of the map that is on screen.
+///
+/// The map can be any size; the camera is a window of `width` by `height`
+/// tiles onto it, kept centred on the player where it can be. Map coordinates
+/// are where something is in the dungeon, screen coordinates where it is
+/// drawn, counted from the top left of the viewport.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Camera {
+    /// the map position shown in the top left corner
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Camera {
+    pub fn new(width: i32, height: i32) -> Self {
+        Camera {
+            x: 0,
+            y: 0,
+            width,
+            height,
+        }
+    }
+
+    /// Centre the view on `(x, y)`, without showing anything past the edges
+    /// of a map of `map_size`. A map smaller than the view sits in the top left.
+    pub fn follow(&mut self, (x, y): (i32, i32), (map_width, map_height): (i32, i32)) {
+        self.x = (x - self.width / 2).clamp(0, (map_width - self.width).max(0));
+        self.y = (y - self.height / 2).clamp(0, (map_height - self.height).max(0));
+    }
+
+    /// where a map position is drawn, if it is on screen at all
+    pub fn to_screen(&self, (x, y): (i32, i32)) -> Option<(i32, i32)> {
+        let (screen_x, screen_y) = (x - self.x, y - self.y);
+        if self.on_screen((screen_x, screen_y)) {
+            Some((screen_x, screen_y))
+        } else {
+            None
+        }
+    }
+
+    /// The map position drawn at a screen position, if it is inside the view.
+    /// With a map smaller than the view this can still be past the map's edge.
+    pub fn to_map(&self, (screen_x, screen_y): (i32, i32)) -> Option<(i32, i32)> {
+        if self.on_screen((screen_x, screen_y)) {
+            Some((screen_x + self.x, screen_y + self.y))
+        } else {
+            None
+        }
+    }
+
+    fn on_screen(&self, (x, y): (i32, i32)) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+}
@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A plain RGB color, so the game core can describe how things look without
+/// depending on any particular rendering library.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Color { r, g, b }
+    }
+}
+
+/// written as `#rrggbb`
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+/*********  PALETTE  **********/
+/// The named colors used by the game, matching the libtcod palette.
+pub const BLACK: Color = Color::new(0, 0, 0);
+pub const DARKEST_GREY: Color = Color::new(31, 31, 31);
+pub const DARKER_GREY: Color = Color::new(63, 63, 63);
+pub const DARK_GREY: Color = Color::new(95, 95, 95);
+pub const GREY: Color = Color::new(127, 127, 127);
+pub const LIGHT_GREY: Color = Color::new(159, 159, 159);
+pub const LIGHTER_GREY: Color = Color::new(191, 191, 191);
+pub const WHITE: Color = Color::new(255, 255, 255);
+
+pub const RED: Color = Color::new(255, 0, 0);
+pub const ORANGE: Color = Color::new(255, 127, 0);
+pub const YELLOW: Color = Color::new(255, 255, 0);
+pub const GREEN: Color = Color::new(0, 255, 0);
+pub const CYAN: Color = Color::new(0, 255, 255);
+pub const SKY: Color = Color::new(0, 191, 255);
+pub const BLUE: Color = Color::new(0, 0, 255);
+pub const VIOLET: Color = Color::new(127, 0, 255);
+
+pub const LIGHT_RED: Color = Color::new(255, 63, 63);
+pub const LIGHT_YELLOW: Color = Color::new(255, 255, 63);
+pub const LIGHT_GREEN: Color = Color::new(63, 255, 63);
+pub const LIGHT_CYAN: Color = Color::new(63, 255, 255);
+pub const LIGHT_BLUE: Color = Color::new(63, 63, 255);
+pub const LIGHT_VIOLET: Color = Color::new(159, 63, 255);
+
+pub const DARK_RED: Color = Color::new(191, 0, 0);
+pub const DARK_GREEN: Color = Color::new(0, 191, 0);
+pub const DARK_BLUE: Color = Color::new(0, 0, 191);
+
+pub const DARKER_RED: Color = Color::new(127, 0, 0);
+pub const DARKER_GREEN: Color = Color::new(0, 127, 0);
+pub const DARKER_ORANGE: Color = Color::new(127, 63, 0);
+
+pub const DESATURATED_GREEN: Color = Color::new(63, 127, 63);
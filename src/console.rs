@@ -0,0 +1,138 @@
+//! What the game needs from whatever draws it and reads the keyboard, so the
+//! same screens work in a tcod window or a terminal.
+
+use crate::colors::Color;
+use crate::keys::Keypress;
+
+/*********  STRUCTURES  *********/
+/// Something happening on the mouse or keyboard
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event {
+    Key(Keypress),
+    Mouse(Mouse),
+}
+
+/// Where the mouse is, as the console cell under it, and which buttons were
+/// just pressed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Mouse {
+    pub x: i32,
+    pub y: i32,
+    pub left: bool,
+    pub right: bool,
+}
+
+/// A grid of character cells, each with a glyph, a foreground and a
+/// background color. Drawing outside the grid does nothing, and nothing shows
+/// until `flush`.
+pub trait Console {
+    /// the width and height in cells
+    fn size(&self) -> (i32, i32);
+
+    /// blank every cell, with a black background
+    fn clear(&mut self);
+
+    /// draw a glyph in a cell, keeping its background
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: Color);
+
+    fn set_background(&mut self, x: i32, y: i32, color: Color);
+
+    fn background(&self, x: i32, y: i32) -> Color;
+
+    /// show everything drawn since the last flush
+    fn flush(&mut self);
+
+    /// draw a glyph in a cell with both its colors
+    fn put_char_ex(&mut self, x: i32, y: i32, glyph: char, foreground: Color, background: Color) {
+        self.put_char(x, y, glyph, foreground);
+        self.set_background(x, y, background);
+    }
+
+    /// write a line of text from `(x, y)`, cut off at the edge
+    fn print(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        for (i, glyph) in text.chars().enumerate() {
+            self.put_char(x + i as i32, y, glyph, color);
+        }
+    }
+
+    /// write a line of text centred on `x`
+    fn print_centered(&mut self, x: i32, y: i32, text: &str, color: Color) {
+        let len = text.chars().count() as i32;
+        self.print(x - len / 2, y, text, color);
+    }
+
+    /// Write text wrapped to lines of `width`, starting at `(x, y)`. Returns how
+    /// many lines it took.
+    fn print_rect(&mut self, x: i32, y: i32, width: i32, text: &str, color: Color) -> i32 {
+        let lines = wrap(text, width);
+        for (i, line) in lines.iter().enumerate() {
+            self.print(x, y + i as i32, line, color);
+        }
+        lines.len() as i32
+    }
+
+    /// Blend the background of a rectangle towards `color`, by `alpha` from 0
+    /// (unchanged) to 1 (all `color`)
+    fn blend_background(
+        &mut self,
+        (x, y): (i32, i32),
+        (width, height): (i32, i32),
+        color: Color,
+        alpha: f32,
+    ) {
+        let mix =
+            |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * alpha).round() as u8;
+        for cell_y in y..y + height {
+            for cell_x in x..x + width {
+                let old = self.background(cell_x, cell_y);
+                let new = Color::new(
+                    mix(old.r, color.r),
+                    mix(old.g, color.g),
+                    mix(old.b, color.b),
+                );
+                self.set_background(cell_x, cell_y, new);
+            }
+        }
+    }
+}
+
+/// Where key presses and mouse movement come from
+pub trait Input {
+    /// The next event, if there is one. Waits no longer than a frame, so the
+    /// screen keeps up with the mouse.
+    fn check_for_event(&mut self) -> Option<Event>;
+
+    /// wait for a key; `None` if the player closes the game instead
+    fn wait_for_keypress(&mut self) -> Option<Keypress>;
+
+    /// whether the player has closed the window, or asked to quit
+    fn is_closed(&self) -> bool;
+}
+
+/// The lines `text` takes when wrapped to `width`: broken at each newline,
+/// and otherwise between words where it can be
+pub fn wrap(text: &str, width: i32) -> Vec<String> {
+    let width = width.max(1) as usize;
+    let mut lines = vec![];
+    for paragraph in text.split('\n') {
+        let mut line: Option<String> = None;
+        for word in paragraph.split(' ') {
+            let mut word: Vec<char> = word.chars().collect();
+            if let Some(current) = line.as_mut() {
+                if current.chars().count() + 1 + word.len() <= width {
+                    current.push(' ');
+                    current.extend(word);
+                    continue;
+                }
+                lines.extend(line.take());
+            }
+            // start a new line with it, breaking it up if it's too long for one
+            while word.len() > width {
+                lines.push(word.drain(..width).collect());
+            }
+            line = Some(word.into_iter().collect());
+        }
+        lines.push(line.unwrap_or_default());
+    }
+    lines
+}
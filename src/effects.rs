@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::colors::*;
+
+/*********  STRUCTURES  *********/
+/// Something that lasts on a fighter for a number of turns
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatusEffect {
+    pub kind: EffectKind,
+    /// how many more turns it lasts
+    pub turns: i32,
+    /// how strong it is: the damage or healing each turn, or the power
+    /// added. Stun, haste and slow don't use it.
+    #[serde(default)]
+    pub magnitude: i32,
+}
+
+/// The kinds of status effect, and how each one combines with itself when it's
+/// applied again:
+///
+/// - poison: the turns add up, and the stronger poison is kept
+/// - strength: the bonuses add up, and it lasts as long as the longer one
+/// - everything else: whichever lasts longer, and the stronger one, is kept
+///
+/// Haste and slow cancel each other out, so applying one removes the other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EffectKind {
+    /// loses `magnitude` hp each turn
+    Poison,
+    /// gets `magnitude` hp back each turn
+    Regeneration,
+    /// loses its turns
+    Stun,
+    /// acts twice as often
+    Haste,
+    /// acts half as often
+    Slow,
+    /// hits `magnitude` harder
+    Strength,
+}
+
+impl StatusEffect {
+    pub fn new(kind: EffectKind, turns: i32, magnitude: i32) -> Self {
+        StatusEffect {
+            kind,
+            turns,
+            magnitude,
+        }
+    }
+}
+
+impl EffectKind {
+    /// the effect this one cancels, if any
+    fn opposite(self) -> Option<EffectKind> {
+        match self {
+            EffectKind::Haste => Some(EffectKind::Slow),
+            EffectKind::Slow => Some(EffectKind::Haste),
+            _ => None,
+        }
+    }
+
+    /// how to describe something having it, e.g. "poisoned"
+    pub fn adjective(self) -> &'static str {
+        use EffectKind::*;
+        match self {
+            Poison => "poisoned",
+            Regeneration => "regenerating",
+            Stun => "stunned",
+            Haste => "hasted",
+            Slow => "slowed",
+            Strength => "strengthened",
+        }
+    }
+
+    /// the color it is shown in
+    pub fn color(self) -> Color {
+        use EffectKind::*;
+        match self {
+            Poison => LIGHT_GREEN,
+            Regeneration => LIGHT_VIOLET,
+            Stun => LIGHT_YELLOW,
+            Haste => SKY,
+            Slow => GREY,
+            Strength => ORANGE,
+        }
+    }
+}
+
+impl fmt::Display for EffectKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let adjective = self.adjective();
+        let mut chars = adjective.chars();
+        match chars.next() {
+            Some(first) => write!(f, "{}{}", first.to_uppercase(), chars.as_str()),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Put `effect` on a fighter with the given `effects`, combining it with one
+/// of the same kind already there (see `EffectKind` for the rules)
+pub fn add_effect(effects: &mut Vec<StatusEffect>, effect: StatusEffect) {
+    if let Some(opposite) = effect.kind.opposite() {
+        if effects.iter().any(|existing| existing.kind == opposite) {
+            // the two cancel out, leaving neither
+            effects.retain(|existing| existing.kind != opposite);
+            return;
+        }
+    }
+    match effects
+        .iter_mut()
+        .find(|existing| existing.kind == effect.kind)
+    {
+        Some(existing) => match effect.kind {
+            EffectKind::Poison => {
+                existing.turns += effect.turns;
+                existing.magnitude = existing.magnitude.max(effect.magnitude);
+            }
+            EffectKind::Strength => {
+                existing.turns = existing.turns.max(effect.turns);
+                existing.magnitude += effect.magnitude;
+            }
+            _ => {
+                existing.turns = existing.turns.max(effect.turns);
+                existing.magnitude = existing.magnitude.max(effect.magnitude);
+            }
+        },
+        None => effects.push(effect),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use EffectKind::*;
+
+    fn applied(effects: &[StatusEffect]) -> Vec<StatusEffect> {
+        let mut on = vec![];
+        for &effect in effects {
+            add_effect(&mut on, effect);
+        }
+        on
+    }
+
+    #[test]
+    fn reapplying_refreshes_instead_of_stacking() {
+        assert_eq!(
+            applied(&[
+                StatusEffect::new(Haste, 5, 0),
+                StatusEffect::new(Haste, 8, 0)
+            ]),
+            vec![StatusEffect::new(Haste, 8, 0)]
+        );
+        // a shorter one doesn't cut the longer one short
+        assert_eq!(
+            applied(&[
+                StatusEffect::new(Regeneration, 8, 1),
+                StatusEffect::new(Regeneration, 3, 2)
+            ]),
+            vec![StatusEffect::new(Regeneration, 8, 2)]
+        );
+        assert_eq!(
+            applied(&[StatusEffect::new(Stun, 2, 0), StatusEffect::new(Stun, 1, 0)]),
+            vec![StatusEffect::new(Stun, 2, 0)]
+        );
+    }
+
+    #[test]
+    fn poison_adds_up_its_turns() {
+        assert_eq!(
+            applied(&[
+                StatusEffect::new(Poison, 4, 1),
+                StatusEffect::new(Poison, 3, 2)
+            ]),
+            vec![StatusEffect::new(Poison, 7, 2)]
+        );
+    }
+
+    #[test]
+    fn strength_adds_up_its_bonus() {
+        assert_eq!(
+            applied(&[
+                StatusEffect::new(Strength, 30, 3),
+                StatusEffect::new(Strength, 10, 3)
+            ]),
+            vec![StatusEffect::new(Strength, 30, 6)]
+        );
+    }
+
+    #[test]
+    fn haste_and_slow_cancel_out() {
+        assert_eq!(
+            applied(&[
+                StatusEffect::new(Haste, 5, 0),
+                StatusEffect::new(Slow, 5, 0)
+            ]),
+            vec![]
+        );
+        // and other kinds are left alone
+        assert_eq!(
+            applied(&[
+                StatusEffect::new(Slow, 5, 0),
+                StatusEffect::new(Poison, 2, 1),
+                StatusEffect::new(Haste, 5, 0)
+            ]),
+            vec![StatusEffect::new(Poison, 2, 1)]
+        );
+    }
+}
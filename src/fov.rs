@@ -0,0 +1,217 @@
+/// A field-of-view map: which tiles can be seen through and which are
+/// currently visible from the last computed origin.
+///
+/// This mirrors the small part of libtcod's `Map` the game uses, so the
+/// simulation can work out what monsters and the player can see without a
+/// window or any rendering library.
+#[derive(Clone, Debug)]
+pub struct FovMap {
+    width: i32,
+    height: i32,
+    transparent: Vec<bool>,
+    walkable: Vec<bool>,
+    in_fov: Vec<bool>,
+}
+
+impl FovMap {
+    pub fn new(width: i32, height: i32) -> Self {
+        let size = (width * height) as usize;
+        FovMap {
+            width,
+            height,
+            transparent: vec![false; size],
+            walkable: vec![false; size],
+            in_fov: vec![false; size],
+        }
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        (self.width, self.height)
+    }
+
+    /// set the properties of a single cell
+    pub fn set(&mut self, x: i32, y: i32, transparent: bool, walkable: bool) {
+        let index = self.index(x, y);
+        self.transparent[index] = transparent;
+        self.walkable[index] = walkable;
+    }
+
+    pub fn is_transparent(&self, x: i32, y: i32) -> bool {
+        self.transparent[self.index(x, y)]
+    }
+
+    pub fn is_walkable(&self, x: i32, y: i32) -> bool {
+        self.walkable[self.index(x, y)]
+    }
+
+    /// was this cell visible from the origin of the last `compute_fov`
+    pub fn is_in_fov(&self, x: i32, y: i32) -> bool {
+        self.in_bounds(x, y) && self.in_fov[self.index(x, y)]
+    }
+
+    /// Recompute the visible cells from `(origin_x, origin_y)`.
+    ///
+    /// Rays are cast from the origin to every cell on the edge of the area
+    /// covered by `radius` (0 means unlimited), stopping at the first opaque
+    /// cell. With `light_walls` the opaque cells that stop a ray are visible too.
+    pub fn compute_fov(&mut self, origin_x: i32, origin_y: i32, radius: i32, light_walls: bool) {
+        for cell in self.in_fov.iter_mut() {
+            *cell = false;
+        }
+        if !self.in_bounds(origin_x, origin_y) {
+            return;
+        }
+        let origin = self.index(origin_x, origin_y);
+        self.in_fov[origin] = true;
+
+        // the bounding box the rays are cast to
+        let (x_min, y_min, x_max, y_max) = if radius > 0 {
+            (
+                (origin_x - radius).max(0),
+                (origin_y - radius).max(0),
+                (origin_x + radius).min(self.width - 1),
+                (origin_y + radius).min(self.height - 1),
+            )
+        } else {
+            (0, 0, self.width - 1, self.height - 1)
+        };
+
+        for x in x_min..=x_max {
+            self.cast_ray(origin_x, origin_y, x, y_min, radius, light_walls);
+            self.cast_ray(origin_x, origin_y, x, y_max, radius, light_walls);
+        }
+        for y in (y_min + 1)..y_max {
+            self.cast_ray(origin_x, origin_y, x_min, y, radius, light_walls);
+            self.cast_ray(origin_x, origin_y, x_max, y, radius, light_walls);
+        }
+
+        if light_walls {
+            self.light_walls(origin_x, origin_y, (x_min, y_min, x_max, y_max));
+        }
+    }
+
+    /// walk a straight line from the origin, marking cells as visible until
+    /// something blocks the view or the radius is reached
+    fn cast_ray(
+        &mut self,
+        origin_x: i32,
+        origin_y: i32,
+        x: i32,
+        y: i32,
+        radius: i32,
+        light_walls: bool,
+    ) {
+        let radius_squared = radius * radius;
+        for (cx, cy) in Line::new((origin_x, origin_y), (x, y)).skip(1) {
+            if !self.in_bounds(cx, cy) {
+                return;
+            }
+            let dx = cx - origin_x;
+            let dy = cy - origin_y;
+            if radius > 0 && dx * dx + dy * dy > radius_squared {
+                return;
+            }
+            let index = self.index(cx, cy);
+            if self.transparent[index] {
+                self.in_fov[index] = true;
+            } else {
+                if light_walls {
+                    self.in_fov[index] = true;
+                }
+                return;
+            }
+        }
+    }
+
+    /// Light opaque cells that sit just behind a visible open cell, looking
+    /// away from the origin. Rays can miss these at shallow angles, which would
+    /// leave holes in otherwise visible walls.
+    fn light_walls(&mut self, origin_x: i32, origin_y: i32, bounds: (i32, i32, i32, i32)) {
+        let (x_min, y_min, x_max, y_max) = bounds;
+        let mut lit = vec![];
+        for y in y_min..=y_max {
+            for x in x_min..=x_max {
+                let index = self.index(x, y);
+                if !self.in_fov[index] || !self.transparent[index] {
+                    continue;
+                }
+                let dx = (x - origin_x).signum();
+                let dy = (y - origin_y).signum();
+                for &(nx, ny) in &[(x + dx, y), (x, y + dy), (x + dx, y + dy)] {
+                    if self.in_bounds(nx, ny) && !self.transparent[self.index(nx, ny)] {
+                        lit.push(self.index(nx, ny));
+                    }
+                }
+            }
+        }
+        for index in lit {
+            self.in_fov[index] = true;
+        }
+    }
+
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width && y < self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y * self.width + x) as usize
+    }
+}
+
+/// The cells on a straight line between two points (Bresenham), including both
+/// end points.
+#[derive(Clone, Debug)]
+pub struct Line {
+    x: i32,
+    y: i32,
+    end: (i32, i32),
+    dx: i32,
+    dy: i32,
+    step_x: i32,
+    step_y: i32,
+    error: i32,
+    done: bool,
+}
+
+impl Line {
+    pub fn new(start: (i32, i32), end: (i32, i32)) -> Self {
+        let dx = (end.0 - start.0).abs();
+        let dy = -(end.1 - start.1).abs();
+        Line {
+            x: start.0,
+            y: start.1,
+            end,
+            dx,
+            dy,
+            step_x: if start.0 < end.0 { 1 } else { -1 },
+            step_y: if start.1 < end.1 { 1 } else { -1 },
+            error: dx + dy,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Line {
+    type Item = (i32, i32);
+
+    fn next(&mut self) -> Option<(i32, i32)> {
+        if self.done {
+            return None;
+        }
+        let current = (self.x, self.y);
+        if current == self.end {
+            self.done = true;
+        } else {
+            let error2 = 2 * self.error;
+            if error2 >= self.dy {
+                self.error += self.dy;
+                self.x += self.step_x;
+            }
+            if error2 <= self.dx {
+                self.error += self.dx;
+                self.y += self.step_y;
+            }
+        }
+        Some(current)
+    }
+}
@@ -0,0 +1,1128 @@
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg32;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+
+use crate::colors::*;
+use crate::effects::EffectKind;
+use crate::fov::FovMap;
+use crate::map::{is_blocked, make_map, Map, TileKind, MAP_HEIGHT, MAP_WIDTH};
+use crate::messages::Messages;
+use crate::object::{Ai, DeathCallback, Fighter, Object};
+use crate::path::find_path;
+use crate::spawns::SpawnTable;
+use crate::world::{Bundle, Entity, World};
+
+/*********  CONSTANTS  **********/
+// Lighting and FOV
+pub const FOV_LIGHT_WALLS: bool = true; // light walls or not
+pub const TORCH_RADIUS: i32 = 10;
+// experience and level-ups
+pub const LEVEL_UP_BASE: i32 = 200;
+pub const LEVEL_UP_FACTOR: i32 = 150;
+/// what each level-up choice adds
+pub const LEVEL_UP_HP: i32 = 20;
+pub const LEVEL_UP_POWER: i32 = 1;
+pub const LEVEL_UP_DEFENSE: i32 = 1;
+// monster behaviour
+/// monsters with less than this percentage of their hp left run away
+pub const FLEE_HP_PERCENT: i32 = 30;
+/// ranged monsters try to keep at least this far from the player
+pub const RANGED_MIN_DISTANCE: i32 = 3;
+/// pack monsters wandering about keep within this distance of each other
+pub const PACK_DISTANCE: f32 = 3.0;
+/// how far from the player a search looks, and the chance out of 100 of
+/// finding each secret door there
+pub const SEARCH_RADIUS: i32 = 2;
+pub const SEARCH_CHANCE: u32 = 50;
+// time
+/// How much energy a fighter gains each tick at normal speed. Hasted
+/// fighters gain twice as much, slowed ones half.
+pub const NORMAL_SPEED: i32 = 10;
+/// the ticks in a turn; status effects count down once a turn
+pub const TICKS_PER_TURN: u32 = 10;
+/// a fighter can act once it has this much energy
+pub const ENERGY_TO_ACT: i32 = 100;
+/// The energy each kind of action takes. At normal speed a fighter gains
+/// `NORMAL_SPEED * TICKS_PER_TURN` a turn, enough for one move.
+pub const MOVE_COST: i32 = 100;
+pub const ATTACK_COST: i32 = 120;
+pub const WAIT_COST: i32 = 100;
+/// drinking a potion is quick; reading a scroll or changing equipment isn't
+pub const QUAFF_COST: i32 = 50;
+pub const USE_ITEM_COST: i32 = 100;
+pub const PICK_UP_COST: i32 = 50;
+pub const DROP_COST: i32 = 50;
+pub const SEARCH_COST: i32 = 100;
+
+/*********  STRUCTURES  *********/
+/// The random number generator used for everything in a game. It is seeded once
+/// at the start, so a seed always replays the same dungeon.
+pub type GameRng = Pcg32;
+
+/// Everything about the current game that isn't an object
+#[derive(Serialize, Deserialize)]
+pub struct Game {
+    pub map: Map,
+    /// rebuilt from the map rather than saved, see `initialise_fov`
+    #[serde(skip, default = "new_fov")]
+    pub fov: FovMap,
+    /// the seed this game's `rng` was started from
+    pub seed: u64,
+    pub rng: GameRng,
+    pub messages: Messages,
+    /// how many turns have gone by so far
+    pub turn: u32,
+    /// how many ticks into the current turn it is
+    pub tick: u32,
+    /// the items the player is carrying, at most `INVENTORY_SIZE` of them
+    pub inventory: Vec<Bundle>,
+    /// how deep the player is, starting from 1
+    pub dungeon_level: u32,
+    /// the monsters each new level is filled with. Kept with the game, so a
+    /// saved game carries on with the table it was started with.
+    pub spawns: SpawnTable,
+}
+
+/// Options for what the player did this frame
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlayerAction {
+    TookTurn,
+    DidntTakeTurn,
+    Exit,
+}
+
+/// The stat the player raises when they level up
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelUpChoice {
+    /// more hit points
+    Constitution,
+    /// more attack power
+    Strength,
+    /// more defense
+    Agility,
+}
+
+/// Create a new game from `seed`: the player, a freshly generated map and its FOV.
+/// Monsters are drawn from `spawns`.
+pub fn new_game(seed: u64, spawns: SpawnTable) -> (Game, World) {
+    // Initilize Player Properties
+    let mut player = Bundle::new(Object::new(0, 0, '@', WHITE, true, "player"));
+    player.object.alive = true;
+    player.fighter = Some(Fighter {
+        base_max_hp: 30,
+        hp: 30,
+        base_defense: 2,
+        base_power: 5,
+        xp: 0,
+        on_death: DeathCallback::Player,
+        effects: vec![],
+        on_hit: None,
+        speed: NORMAL_SPEED,
+        // enough to go first
+        energy: ENERGY_TO_ACT,
+    });
+
+    // everything in the game, starting with the player
+    let mut world = World::new(player);
+    let mut rng = GameRng::seed_from_u64(seed);
+    let generator = spawns.choose_map_style(1, &mut rng).generator();
+    let map = make_map(&mut world, &mut rng, 1, &spawns, generator.as_ref());
+    let mut game = Game {
+        map,
+        fov: new_fov(),
+        seed,
+        rng,
+        messages: Messages::new(),
+        turn: 0,
+        tick: 0,
+        inventory: vec![],
+        dungeon_level: 1,
+        spawns,
+    };
+
+    initialise_fov(&mut game);
+    update_fov(&mut game, &world);
+
+    // a warm welcoming message!
+    game.messages.add(
+        game.turn,
+        "Welcome stranger! Prepare to perish in the Tombs of the Ancient Kings.",
+        RED,
+    );
+
+    (game, world)
+}
+
+/// an empty FOV map the size of the dungeon
+fn new_fov() -> FovMap {
+    FovMap::new(MAP_WIDTH, MAP_HEIGHT)
+}
+
+/// the experience the player needs to get from `level` to the next one
+pub fn level_up_xp(level: i32) -> i32 {
+    LEVEL_UP_BASE + level * LEVEL_UP_FACTOR
+}
+
+/// whether the player has enough experience to level up
+pub fn can_level_up(world: &World) -> bool {
+    let level = world.objects[world.player].level;
+    world
+        .fighters
+        .get(world.player)
+        .is_some_and(|f| f.xp >= level_up_xp(level))
+}
+
+/// Spend the experience for one level, raising the chosen stat
+pub fn level_up(choice: LevelUpChoice, game: &mut Game, world: &mut World) {
+    let player = &mut world.objects[world.player];
+    let level = player.level;
+    if let Some(fighter) = world.fighters.get_mut(world.player) {
+        fighter.xp -= level_up_xp(level);
+        match choice {
+            LevelUpChoice::Constitution => {
+                fighter.base_max_hp += LEVEL_UP_HP;
+                fighter.hp += LEVEL_UP_HP;
+            }
+            LevelUpChoice::Strength => fighter.base_power += LEVEL_UP_POWER,
+            LevelUpChoice::Agility => fighter.base_defense += LEVEL_UP_DEFENSE,
+        }
+        player.level += 1;
+        game.messages.add(
+            game.turn,
+            format!(
+                "Your battle skills grow stronger! You reached level {}!",
+                player.level
+            ),
+            YELLOW,
+        );
+    }
+}
+
+/// Advance to the next level, if the player is standing on the stairs.
+/// Returns whether they were.
+pub fn take_stairs(game: &mut Game, world: &mut World) -> bool {
+    let on_stairs = world
+        .objects
+        .values()
+        .any(|object| object.pos() == world.objects[world.player].pos() && object.name == "stairs");
+    if on_stairs {
+        world.spend_energy(world.player, MOVE_COST);
+        next_level(game, world);
+    }
+    on_stairs
+}
+
+/// Generate the next, deeper level and move the player into it. Everything on
+/// the old level but the player is left behind.
+pub fn next_level(game: &mut Game, world: &mut World) {
+    game.messages.add(
+        game.turn,
+        "You take a moment to rest, and recover your strength.",
+        VIOLET,
+    );
+    let heal_hp = world.max_hp(world.player, game) / 2;
+    world.heal(world.player, heal_hp, game);
+
+    game.messages.add(
+        game.turn,
+        "After a rare moment of peace, you descend deeper into \
+         the heart of the dungeon...",
+        RED,
+    );
+    game.dungeon_level += 1;
+    let player = world.player;
+    world.retain(|entity| entity == player);
+    let generator = game
+        .spawns
+        .choose_map_style(game.dungeon_level, &mut game.rng)
+        .generator();
+    game.map = make_map(
+        world,
+        &mut game.rng,
+        game.dungeon_level,
+        &game.spawns,
+        generator.as_ref(),
+    );
+    initialise_fov(game);
+    update_fov(game, world);
+}
+
+/// populate the FOV map, according to the generated map
+pub fn initialise_fov(game: &mut Game) {
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            game.fov.set(
+                x,
+                y,
+                !game.map[x as usize][y as usize].block_sight(),
+                !game.map[x as usize][y as usize].blocked(),
+            );
+        }
+    }
+}
+
+/// recompute what the player can see, and mark everything visible as explored
+pub fn update_fov(game: &mut Game, world: &World) {
+    let player = &world.objects[world.player];
+    game.fov
+        .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS);
+
+    for y in 0..MAP_HEIGHT {
+        for x in 0..MAP_WIDTH {
+            if game.fov.is_in_fov(x, y) {
+                // since it's visible, explore it
+                game.map[x as usize][y as usize].explored = true;
+            }
+        }
+    }
+}
+
+/// Finish the player's action: update what they can see, then let everyone
+/// else act until it's the player's turn again
+pub fn end_player_turn(game: &mut Game, world: &mut World) {
+    update_fov(game, world);
+    process_turns(game, world);
+}
+
+/// Run the turn queue until the player is the next to act, or dead.
+///
+/// Every tick, each fighter gains energy according to its speed. Whoever has
+/// enough energy to act does, the one with the most first (the player wins a
+/// tie, and otherwise whoever's slot comes first), and their action uses some
+/// of it up. The player is an actor like any other; this just stops to wait for
+/// them to choose what to do.
+pub fn process_turns(game: &mut Game, world: &mut World) {
+    while world.objects[world.player].alive {
+        let next = world
+            .fighters
+            .entities()
+            .filter(|&id| is_actor(id, world) && world.energy(id) >= ENERGY_TO_ACT)
+            .max_by_key(|&id| (world.energy(id), id == world.player, cmp::Reverse(id)));
+        let id = match next {
+            Some(id) => id,
+            None => {
+                tick(game, world);
+                continue;
+            }
+        };
+
+        if world.has_effect(id, EffectKind::Stun) {
+            if id == world.player {
+                game.messages
+                    .add(game.turn, "You are stunned and can't act!", LIGHT_YELLOW);
+            }
+            lose_turn_to_stun(id, world);
+        } else if id == world.player {
+            // over to them
+            return;
+        } else {
+            let energy = world.energy(id);
+            ai_take_turn(id, game, world);
+            // doing nothing takes as long as waiting
+            if world.energy(id) == energy {
+                world.spend_energy(id, WAIT_COST);
+            }
+        }
+    }
+}
+
+/// whether it takes turns: the player, and any monster that's alive
+fn is_actor(id: Entity, world: &World) -> bool {
+    world.objects[id].alive && world.fighters.has(id) && (id == world.player || world.ais.has(id))
+}
+
+/// Give every actor its energy for one tick. At the end of a turn, status
+/// effects count down.
+fn tick(game: &mut Game, world: &mut World) {
+    let actors: Vec<_> = world
+        .fighters
+        .entities()
+        .filter(|&id| is_actor(id, world))
+        .collect();
+    for id in actors {
+        let speed = world.speed(id);
+        if let Some(fighter) = world.fighters.get_mut(id) {
+            fighter.energy += speed;
+        }
+    }
+    game.tick += 1;
+    if game.tick >= TICKS_PER_TURN {
+        game.tick = 0;
+        let fighters: Vec<_> = world.fighters.entities().collect();
+        for id in fighters {
+            tick_effects(id, game, world);
+        }
+        game.turn += 1;
+    }
+}
+
+/// Stuns last for a number of the stunned one's own turns rather than the
+/// world's, so that a stun always costs a turn, however fast it is
+fn lose_turn_to_stun(id: Entity, world: &mut World) {
+    world.spend_energy(id, WAIT_COST);
+    if let Some(fighter) = world.fighters.get_mut(id) {
+        for effect in &mut fighter.effects {
+            if effect.kind == EffectKind::Stun {
+                effect.turns -= 1;
+            }
+        }
+        fighter
+            .effects
+            .retain(|effect| effect.kind != EffectKind::Stun || effect.turns > 0);
+    }
+}
+
+/// Apply a turn of an object's status effects: poison hurts, regeneration
+/// heals, and everything but stun counts down, ending when it runs out
+fn tick_effects(id: Entity, game: &mut Game, world: &mut World) {
+    let effects = match world.fighters.get(id) {
+        Some(fighter) if world.objects[id].alive => fighter.effects.clone(),
+        _ => return,
+    };
+    let is_player = id == world.player;
+    let visible = game.fov.is_in_fov(world.objects[id].x, world.objects[id].y);
+
+    for effect in &effects {
+        match effect.kind {
+            EffectKind::Poison if effect.magnitude > 0 => {
+                if is_player {
+                    game.messages.add(
+                        game.turn,
+                        format!("The poison burns for {} hit points.", effect.magnitude),
+                        effect.kind.color(),
+                    );
+                } else if visible {
+                    game.messages.add(
+                        game.turn,
+                        format!(
+                            "{} suffers {} hit points from poison.",
+                            world.objects[id].name, effect.magnitude
+                        ),
+                        effect.kind.color(),
+                    );
+                }
+                // nobody gets the experience for something poison kills
+                world.take_damage(id, effect.magnitude, game);
+            }
+            EffectKind::Regeneration => world.heal(id, effect.magnitude, game),
+            _ => {}
+        }
+    }
+
+    if let Some(fighter) = world.fighters.get_mut(id) {
+        let mut expired = vec![];
+        for effect in &mut fighter.effects {
+            if effect.kind != EffectKind::Stun {
+                effect.turns -= 1;
+                if effect.turns <= 0 {
+                    expired.push(effect.kind);
+                }
+            }
+        }
+        fighter.effects.retain(|effect| effect.turns > 0);
+        if is_player {
+            for kind in expired {
+                game.messages.add(
+                    game.turn,
+                    format!("You are no longer {}.", kind.adjective()),
+                    WHITE,
+                );
+            }
+        }
+    }
+}
+
+/********** GENERIC FUNCTIONS ***********/
+
+/// change what a tile is, keeping the FOV map in sync with it
+pub fn set_tile(x: i32, y: i32, kind: TileKind, game: &mut Game) {
+    game.map[x as usize][y as usize].kind = kind;
+    game.fov
+        .set(x, y, !kind.blocks_sight(), !kind.blocks_movement());
+}
+
+/// open the door at (x, y), if there is a closed one there. Returns whether it did.
+pub fn open_door(x: i32, y: i32, game: &mut Game) -> bool {
+    if game.map[x as usize][y as usize].kind == TileKind::ClosedDoor {
+        set_tile(x, y, TileKind::OpenDoor, game);
+        true
+    } else {
+        false
+    }
+}
+
+/// move by the given amount, if the destination is not blocked. Walking into
+/// a closed door opens it instead. Bumping into a wall takes as long as a step.
+pub fn move_by(id: Entity, dx: i32, dy: i32, game: &mut Game, world: &mut World) {
+    world.spend_energy(id, MOVE_COST);
+    let (x, y) = world.objects[id].pos();
+    let (x2, y2) = (x + dx, y + dy);
+    if x2 < 0 || y2 < 0 || x2 >= MAP_WIDTH || y2 >= MAP_HEIGHT {
+        return;
+    }
+    if open_door(x2, y2, game) {
+        if id == world.player {
+            game.messages.add(game.turn, "You open the door.", WHITE);
+        } else if game.fov.is_in_fov(x, y) {
+            let message = format!("The {} opens a door.", world.objects[id].name);
+            game.messages.add(game.turn, message, WHITE);
+        }
+    } else if !is_blocked(x2, y2, &game.map, &world.objects) {
+        world.objects[id].set_pos(x2, y2);
+    }
+}
+
+/// Look around the player for secret doors. Each one close enough has an
+/// even chance of being found.
+pub fn search(game: &mut Game, world: &mut World) {
+    world.spend_energy(world.player, SEARCH_COST);
+    let (player_x, player_y) = world.objects[world.player].pos();
+    let mut found = 0;
+    for x in (player_x - SEARCH_RADIUS)..=(player_x + SEARCH_RADIUS) {
+        for y in (player_y - SEARCH_RADIUS)..=(player_y + SEARCH_RADIUS) {
+            let in_map = x >= 0 && y >= 0 && x < MAP_WIDTH && y < MAP_HEIGHT;
+            if in_map
+                && game.map[x as usize][y as usize].kind == TileKind::SecretDoor
+                && game.rng.gen_range(0..100) < SEARCH_CHANCE
+            {
+                set_tile(x, y, TileKind::ClosedDoor, game);
+                found += 1;
+            }
+        }
+    }
+    if found > 0 {
+        game.messages
+            .add(game.turn, "You find a secret door!", LIGHT_GREEN);
+    } else {
+        game.messages
+            .add(game.turn, "You search, but find nothing.", WHITE);
+    }
+}
+
+/// let a turn go by, doing nothing
+pub fn wait(id: Entity, world: &mut World) {
+    world.spend_energy(id, WAIT_COST);
+}
+
+/// move the player and potentially attack a target
+pub fn player_move_or_attack(dx: i32, dy: i32, game: &mut Game, world: &mut World) {
+    // the coordinates the player is moving to/attacking
+    let x = world.objects[world.player].x + dx;
+    let y = world.objects[world.player].y + dy;
+
+    // try to find an attackable target there
+    let target_id = world
+        .fighters
+        .entities()
+        .find(|&id| world.objects[id].pos() == (x, y));
+
+    // attack if target found, move otherwise
+    match target_id {
+        Some(target_id) => {
+            world.attack(world.player, target_id, game);
+        }
+        None => move_by(world.player, dx, dy, game, world),
+    }
+}
+
+/// move an object to move towards a position
+pub fn move_towards(id: Entity, target_x: i32, target_y: i32, game: &mut Game, world: &mut World) {
+    // vector from this object to the target and distance
+    let dx = target_x - world.objects[id].x;
+    let dy = target_y - world.objects[id].y;
+    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
+
+    // normalize it to length 1 (preserving direction), then round it and convert it to
+    // integer so the movement is restricted to the map grid
+    let dx = (dx as f32 / distance).round() as i32;
+    let dy = (dy as f32 / distance).round() as i32;
+    move_by(id, dx, dy, game, world);
+}
+
+/// Move an object one step along the shortest path to a position, going
+/// around walls and other monsters. If there is no path at all, just head
+/// straight for it.
+pub fn move_astar(id: Entity, target_x: i32, target_y: i32, game: &mut Game, world: &mut World) {
+    match find_path(
+        &game.map,
+        &world.objects,
+        world.objects[id].pos(),
+        (target_x, target_y),
+    ) {
+        Some(path) if !path.is_empty() => {
+            let (x, y) = world.objects[id].pos();
+            let (next_x, next_y) = path[0];
+            move_by(id, next_x - x, next_y - y, game, world);
+        }
+        _ => move_towards(id, target_x, target_y, game, world),
+    }
+}
+
+/// move a single monster
+pub fn ai_take_turn(monster_id: Entity, game: &mut Game, world: &mut World) {
+    use Ai::*;
+    // if you can see it, it can see you: remember where the player was
+    let (monster_x, monster_y) = world.objects[monster_id].pos();
+    if game.fov.is_in_fov(monster_x, monster_y) {
+        let player_pos = world.objects[world.player].pos();
+        world.insert(monster_id, |w| &mut w.last_seen_player, player_pos);
+        // and calls the rest of its pack over
+        for mate in pack_mates(monster_id, world) {
+            world.insert(mate, |w| &mut w.last_seen_player, player_pos);
+        }
+    }
+
+    if let Some(mut ai) = world.ais.remove(monster_id) {
+        // a badly hurt monster stops whatever it was doing and runs
+        if let Basic | Ranged { .. } = ai {
+            if is_badly_hurt(monster_id, game, world) {
+                game.messages.add(
+                    game.turn,
+                    format!("The {} turns to flee!", world.objects[monster_id].name),
+                    LIGHT_YELLOW,
+                );
+                ai = Fleeing {
+                    previous_ai: Box::new(ai),
+                };
+            }
+        }
+        let new_ai = match ai {
+            Basic => ai_basic(monster_id, game, world),
+            Ranged { range } => ai_ranged(monster_id, game, world, range),
+            Fleeing { previous_ai } => ai_fleeing(monster_id, game, world, previous_ai),
+            Confused { previous_ai, turns } => {
+                ai_confused(monster_id, game, world, previous_ai, turns)
+            }
+        };
+        world.insert(monster_id, |w| &mut w.ais, new_ai);
+    }
+}
+
+/// a basic monster takes its turn.  If you can see it, it can see you
+fn ai_basic(monster_id: Entity, game: &mut Game, world: &mut World) -> Ai {
+    let (monster_x, monster_y) = world.objects[monster_id].pos();
+    if game.fov.is_in_fov(monster_x, monster_y) {
+        if world.objects[monster_id].distance_to(&world.objects[world.player]) >= 2.0 {
+            // move towards the player if far away
+            let (player_x, player_y) = world.objects[world.player].pos();
+            move_astar(monster_id, player_x, player_y, game, world);
+        } else if world.fighters.get(world.player).is_some_and(|f| f.hp > 0) {
+            // close enough, attack! (if the player is still alive)
+            world.attack(monster_id, world.player, game);
+        }
+    } else {
+        ai_search(monster_id, game, world);
+    }
+    Ai::Basic
+}
+
+/// a ranged monster shoots from a distance, and backs off if the player gets close
+fn ai_ranged(monster_id: Entity, game: &mut Game, world: &mut World, range: i32) -> Ai {
+    let (monster_x, monster_y) = world.objects[monster_id].pos();
+    if game.fov.is_in_fov(monster_x, monster_y) {
+        let distance = world.objects[monster_id].distance_to(&world.objects[world.player]);
+        let player_alive = world.fighters.get(world.player).is_some_and(|f| f.hp > 0);
+        if distance < RANGED_MIN_DISTANCE as f32 {
+            // too close for comfort; if there's nowhere to go, fight back
+            if !step_away(monster_id, game, world) && distance < 2.0 && player_alive {
+                world.attack(monster_id, world.player, game);
+            }
+        } else if distance <= range as f32 {
+            if player_alive {
+                world.attack(monster_id, world.player, game);
+            }
+        } else {
+            // get within range
+            let (player_x, player_y) = world.objects[world.player].pos();
+            move_astar(monster_id, player_x, player_y, game, world);
+        }
+    } else {
+        ai_search(monster_id, game, world);
+    }
+    Ai::Ranged { range }
+}
+
+/// a fleeing monster keeps away from the player until it has recovered
+fn ai_fleeing(monster_id: Entity, game: &mut Game, world: &mut World, previous_ai: Box<Ai>) -> Ai {
+    if !is_badly_hurt(monster_id, game, world) {
+        game.messages.add(
+            game.turn,
+            format!(
+                "The {} regains its courage!",
+                world.objects[monster_id].name
+            ),
+            LIGHT_YELLOW,
+        );
+        return *previous_ai;
+    }
+    let (monster_x, monster_y) = world.objects[monster_id].pos();
+    if game.fov.is_in_fov(monster_x, monster_y) {
+        // run, and if it's cornered, fight
+        let cornered = !step_away(monster_id, game, world);
+        let adjacent = world.objects[monster_id].distance_to(&world.objects[world.player]) < 2.0;
+        if cornered && adjacent && world.fighters.get(world.player).is_some_and(|f| f.hp > 0) {
+            world.attack(monster_id, world.player, game);
+        }
+    }
+    // out of sight it stays put, hiding
+    Ai::Fleeing { previous_ai }
+}
+
+/// a confused monster moves at random, until the confusion wears off
+fn ai_confused(
+    monster_id: Entity,
+    game: &mut Game,
+    world: &mut World,
+    previous_ai: Box<Ai>,
+    turns: i32,
+) -> Ai {
+    if turns >= 0 {
+        // still confused ...
+        // move in a random direction, and decrease the number of turns confused
+        let dx = game.rng.gen_range(-1..=1);
+        let dy = game.rng.gen_range(-1..=1);
+        move_by(monster_id, dx, dy, game, world);
+        Ai::Confused {
+            previous_ai,
+            turns: turns - 1,
+        }
+    } else {
+        // restore the previous AI (this one will be deleted)
+        game.messages.add(
+            game.turn,
+            format!(
+                "The {} is no longer confused!",
+                world.objects[monster_id].name
+            ),
+            RED,
+        );
+        *previous_ai
+    }
+}
+
+/// With the player out of sight, head for where they were last seen. Once
+/// there, or if they've never been seen, just wander about, without straying
+/// from the pack if it's in one.
+fn ai_search(monster_id: Entity, game: &mut Game, world: &mut World) {
+    let nearest_mate = pack_mates(monster_id, world)
+        .into_iter()
+        .map(|mate| world.objects[mate].pos())
+        .min_by_key(|&(x, y)| {
+            let (monster_x, monster_y) = world.objects[monster_id].pos();
+            (x - monster_x).pow(2) + (y - monster_y).pow(2)
+        });
+    match world.last_seen_player.get(monster_id).copied() {
+        Some(pos) if pos != world.objects[monster_id].pos() => {
+            let (x, y) = pos;
+            move_astar(monster_id, x, y, game, world);
+        }
+        _ => {
+            // they're not here: forget about them
+            world.last_seen_player.remove(monster_id);
+            if let Some((x, y)) = nearest_mate {
+                if world.objects[monster_id].distance((x, y)) > PACK_DISTANCE {
+                    move_astar(monster_id, x, y, game, world);
+                    return;
+                }
+            }
+            let dx = game.rng.gen_range(-1..=1);
+            let dy = game.rng.gen_range(-1..=1);
+            move_by(monster_id, dx, dy, game, world);
+        }
+    }
+}
+
+/// Take the step that gets furthest from the player, if any step gets further
+/// at all. Returns whether it moved.
+fn step_away(monster_id: Entity, game: &mut Game, world: &mut World) -> bool {
+    let (x, y) = world.objects[monster_id].pos();
+    let player = world.objects[world.player].pos();
+    let distance_from_player = |(x, y): (i32, i32)| (x - player.0).pow(2) + (y - player.1).pow(2);
+    let best = (-1..=1)
+        .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+        .filter(|&(dx, dy)| !is_blocked(x + dx, y + dy, &game.map, &world.objects))
+        .max_by_key(|&(dx, dy)| distance_from_player((x + dx, y + dy)));
+    match best {
+        Some((dx, dy)) if distance_from_player((x + dx, y + dy)) > distance_from_player((x, y)) => {
+            move_by(monster_id, dx, dy, game, world);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// the other living monsters in the same pack, if it's in one
+fn pack_mates(id: Entity, world: &World) -> Vec<Entity> {
+    match world.packs.get(id) {
+        Some(&pack) => world
+            .packs
+            .iter()
+            .filter(|&(mate, &mate_pack)| mate != id && mate_pack == pack && world.ais.has(mate))
+            .map(|(mate, _)| mate)
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// whether a monster is hurt enough to run away
+fn is_badly_hurt(id: Entity, game: &Game, world: &World) -> bool {
+    world
+        .fighters
+        .get(id)
+        .is_some_and(|f| f.hp * 100 < world.max_hp(id, game) * FLEE_HP_PERCENT)
+}
+
+/// the names of everything the player can see at the given position, separated
+/// by commas
+pub fn get_names_under(x: i32, y: i32, game: &Game, world: &World) -> String {
+    // create a list with the names of all objects at the coordinates and in FOV
+    let names = world
+        .objects
+        .values()
+        .filter(|obj| obj.pos() == (x, y) && game.fov.is_in_fov(obj.x, obj.y))
+        .map(|obj| obj.name.clone())
+        .collect::<Vec<_>>();
+
+    names.join(", ") // join the names, separated by commas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::StatusEffect;
+
+    /// A game with the player at (10, 10) in a big open room, with nothing
+    /// else in it
+    fn arena() -> (Game, World) {
+        let (mut game, mut world) = new_game(1, SpawnTable::default());
+        let player = world.player;
+        world.retain(|entity| entity == player);
+        for x in 0..MAP_WIDTH {
+            for y in 0..MAP_HEIGHT {
+                let border = x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+                let kind = if border {
+                    TileKind::Wall
+                } else {
+                    TileKind::Floor
+                };
+                set_tile(x, y, kind, &mut game);
+            }
+        }
+        world.objects[player].set_pos(10, 10);
+        update_fov(&mut game, &world);
+        (game, world)
+    }
+
+    /// an orc with `hp` of its 10 hit points left
+    fn spawn_monster(world: &mut World, (x, y): (i32, i32), ai: Ai, hp: i32) -> Entity {
+        let mut monster = Bundle::new(Object::new(x, y, 'o', WHITE, true, "orc"));
+        monster.object.alive = true;
+        monster.fighter = Some(Fighter {
+            base_max_hp: 10,
+            hp,
+            base_defense: 0,
+            base_power: 5,
+            xp: 0,
+            on_death: DeathCallback::Monster,
+            effects: vec![],
+            on_hit: None,
+            speed: NORMAL_SPEED,
+            energy: 0,
+        });
+        monster.ai = Some(ai);
+        world.spawn(monster)
+    }
+
+    fn distance_to_player(id: Entity, world: &World) -> f32 {
+        world.objects[id].distance_to(&world.objects[world.player])
+    }
+
+    /// let whole turns go by
+    fn pass_turns(turns: u32, game: &mut Game, world: &mut World) {
+        for _ in 0..turns * TICKS_PER_TURN {
+            tick(game, world);
+        }
+    }
+
+    #[test]
+    fn a_new_game_plays_without_a_screen() {
+        for seed in 0..5 {
+            let (mut game, mut world) = new_game(seed, SpawnTable::default());
+            let player = world.player;
+            process_turns(&mut game, &mut world);
+            for &(dx, dy) in &[(1, 0), (0, 1), (-1, 0), (0, -1), (1, 1)] {
+                player_move_or_attack(dx, dy, &mut game, &mut world);
+                end_player_turn(&mut game, &mut world);
+            }
+            assert!(game.turn > 0, "seed {}", seed);
+            if world.objects[player].alive {
+                assert!(world.energy(player) >= ENERGY_TO_ACT, "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn effects_expire_on_their_last_turn() {
+        let (mut game, mut world) = arena();
+        let player = world.player;
+        world.add_effect(player, StatusEffect::new(EffectKind::Haste, 2, 0));
+        pass_turns(1, &mut game, &mut world);
+        assert!(world.has_effect(player, EffectKind::Haste));
+        assert_eq!(world.fighters[player].effects[0].turns, 1);
+
+        pass_turns(1, &mut game, &mut world);
+        assert!(!world.has_effect(player, EffectKind::Haste));
+        assert_eq!(
+            game.messages
+                .iter()
+                .last()
+                .map(|message| message.text.as_str()),
+            Some("You are no longer hasted.")
+        );
+    }
+
+    #[test]
+    fn poison_hurts_every_turn_it_lasts() {
+        let (mut game, mut world) = arena();
+        let player = world.player;
+        let full_hp = world.fighters[player].hp;
+        world.add_effect(player, StatusEffect::new(EffectKind::Poison, 3, 2));
+        pass_turns(5, &mut game, &mut world);
+        assert_eq!(world.fighters[player].hp, full_hp - 6);
+        assert!(world.fighters[player].effects.is_empty());
+    }
+
+    /// How far a monster of `speed`, under `effect` if any, walks each time
+    /// the player waits a turn. It walks one step per action, towards where
+    /// it saw the player last, well out of their sight.
+    fn steps_per_player_turn(speed: i32, effect: Option<EffectKind>, turns: usize) -> Vec<i32> {
+        let (mut game, mut world) = arena();
+        let monster = spawn_monster(&mut world, (110, 70), Ai::Basic, 10);
+        world.fighters[monster].speed = speed;
+        world.insert(monster, |w| &mut w.last_seen_player, (20, 70));
+        if let Some(kind) = effect {
+            world.add_effect(monster, StatusEffect::new(kind, 1000, 0));
+        }
+        let player = world.player;
+        // the first turn starts with the player ahead, so it doesn't count
+        wait(player, &mut world);
+        process_turns(&mut game, &mut world);
+        let mut steps = vec![];
+        for _ in 0..turns {
+            let x = world.objects[monster].x;
+            wait(player, &mut world);
+            process_turns(&mut game, &mut world);
+            steps.push(x - world.objects[monster].x);
+        }
+        steps
+    }
+
+    #[test]
+    fn speed_decides_how_often_monsters_act() {
+        assert_eq!(steps_per_player_turn(10, None, 6), vec![1; 6]);
+        assert_eq!(steps_per_player_turn(20, None, 6), vec![2; 6]);
+        let slow = steps_per_player_turn(5, None, 6);
+        assert_eq!(slow.iter().sum::<i32>(), 3, "{:?}", slow);
+        assert!(slow.iter().all(|&steps| steps <= 1), "{:?}", slow);
+    }
+
+    #[test]
+    fn haste_and_slow_change_how_often_monsters_act() {
+        assert_eq!(
+            steps_per_player_turn(10, Some(EffectKind::Haste), 6),
+            vec![2; 6]
+        );
+        let slowed = steps_per_player_turn(10, Some(EffectKind::Slow), 6);
+        assert_eq!(slowed.iter().sum::<i32>(), 3, "{:?}", slowed);
+        assert!(slowed.iter().all(|&steps| steps <= 1), "{:?}", slowed);
+    }
+
+    /// the names of whoever attacked the player, in order
+    fn attackers(game: &Game) -> Vec<String> {
+        game.messages
+            .iter()
+            .filter_map(|message| {
+                message
+                    .text
+                    .strip_suffix(" attacks player for 3 hit points.")
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn ties_go_to_the_most_energy_then_the_player_then_the_first_slot() {
+        let (mut game, mut world) = arena();
+        let player = world.player;
+        let a = spawn_monster(&mut world, (11, 10), Ai::Basic, 10);
+        let b = spawn_monster(&mut world, (9, 10), Ai::Basic, 10);
+        // freed slots are reused last first, so see which got the lower one
+        let (first, second) = (a.min(b), a.max(b));
+        world.objects[first].name = "first".into();
+        world.objects[second].name = "second".into();
+
+        // as much energy as the player: the player goes first
+        world.fighters[player].energy = ENERGY_TO_ACT;
+        world.fighters[first].energy = ENERGY_TO_ACT;
+        world.fighters[second].energy = ENERGY_TO_ACT;
+        process_turns(&mut game, &mut world);
+        assert!(attackers(&game).is_empty());
+
+        // between monsters, the one in the first slot
+        world.fighters[player].energy = 0;
+        process_turns(&mut game, &mut world);
+        assert_eq!(attackers(&game), vec!["first", "second"]);
+
+        // but more energy beats both
+        world.fighters[player].energy = ENERGY_TO_ACT;
+        world.fighters[first].energy = ENERGY_TO_ACT;
+        world.fighters[second].energy = ENERGY_TO_ACT + 10;
+        process_turns(&mut game, &mut world);
+        assert_eq!(attackers(&game), vec!["first", "second", "second"]);
+        assert_eq!(world.fighters[player].energy, ENERGY_TO_ACT);
+    }
+
+    #[test]
+    fn monsters_flee_below_the_threshold() {
+        let (mut game, mut world) = arena();
+        // 30% left is still brave enough
+        let brave = spawn_monster(&mut world, (13, 10), Ai::Basic, 3);
+        ai_take_turn(brave, &mut game, &mut world);
+        assert_eq!(world.ais[brave], Ai::Basic);
+        assert!(distance_to_player(brave, &world) < 3.0);
+
+        let hurt = spawn_monster(&mut world, (10, 13), Ai::Basic, 2);
+        ai_take_turn(hurt, &mut game, &mut world);
+        assert_eq!(
+            world.ais[hurt],
+            Ai::Fleeing {
+                previous_ai: Box::new(Ai::Basic)
+            }
+        );
+        assert!(distance_to_player(hurt, &world) > 3.0);
+    }
+
+    #[test]
+    fn fleeing_monsters_regain_their_courage() {
+        let (mut game, mut world) = arena();
+        let fleeing = Ai::Fleeing {
+            previous_ai: Box::new(Ai::Ranged { range: 6 }),
+        };
+        let monster = spawn_monster(&mut world, (14, 10), fleeing.clone(), 2);
+        ai_take_turn(monster, &mut game, &mut world);
+        assert_eq!(world.ais[monster], fleeing);
+
+        world.heal(monster, 10, &game);
+        ai_take_turn(monster, &mut game, &mut world);
+        assert_eq!(world.ais[monster], Ai::Ranged { range: 6 });
+    }
+
+    #[test]
+    fn ranged_monsters_keep_their_distance() {
+        let (mut game, mut world) = arena();
+        let player = world.player;
+        let full_hp = world.fighters[player].hp;
+
+        // too close: back off rather than shoot
+        let archer = spawn_monster(&mut world, (12, 10), Ai::Ranged { range: 6 }, 10);
+        ai_take_turn(archer, &mut game, &mut world);
+        assert!(distance_to_player(archer, &world) > 2.0);
+        assert_eq!(world.fighters[player].hp, full_hp);
+
+        // in range: shoot
+        world.objects[archer].set_pos(15, 10);
+        ai_take_turn(archer, &mut game, &mut world);
+        assert_eq!(world.objects[archer].pos(), (15, 10));
+        assert!(world.fighters[player].hp < full_hp);
+
+        // out of range: come closer
+        world.objects[archer].set_pos(18, 10);
+        ai_take_turn(archer, &mut game, &mut world);
+        assert_eq!(world.objects[archer].pos(), (17, 10));
+    }
+
+    #[test]
+    fn monsters_walk_to_where_they_last_saw_the_player() {
+        let (mut game, mut world) = arena();
+        // well out of sight
+        let monster = spawn_monster(&mut world, (40, 10), Ai::Basic, 10);
+        world.insert(monster, |w| &mut w.last_seen_player, (30, 10));
+        for x in (30..40).rev() {
+            ai_take_turn(monster, &mut game, &mut world);
+            assert_eq!(world.objects[monster].pos(), (x, 10));
+        }
+        // and once there, give up on them
+        ai_take_turn(monster, &mut game, &mut world);
+        assert!(!world.last_seen_player.has(monster));
+    }
+
+    #[test]
+    fn a_pack_shares_where_it_saw_the_player() {
+        let (mut game, mut world) = arena();
+        let lookout = spawn_monster(&mut world, (14, 10), Ai::Basic, 10);
+        let mate = spawn_monster(&mut world, (60, 40), Ai::Basic, 10);
+        let stranger = spawn_monster(&mut world, (60, 50), Ai::Basic, 10);
+        world.insert(lookout, |w| &mut w.packs, lookout);
+        world.insert(mate, |w| &mut w.packs, lookout);
+
+        ai_take_turn(lookout, &mut game, &mut world);
+        assert_eq!(world.last_seen_player.get(mate), Some(&(10, 10)));
+        assert_eq!(world.last_seen_player.get(stranger), None);
+
+        // and the mate heads that way
+        ai_take_turn(mate, &mut game, &mut world);
+        assert_eq!(world.objects[mate].pos(), (59, 39));
+    }
+
+    #[test]
+    fn packs_spawn_together() {
+        let spawns = SpawnTable::parse(
+            r#"(
+                max_per_room: [(from: 1, value: 2)],
+                monsters: [(
+                    name: "jackal", glyph: 'j', color: (r: 191, g: 127, b: 63),
+                    hp: 4, defense: 0, power: 2, xp: 15, ai: Basic, on_death: Monster,
+                    pack: (min: 2, max: 3), weights: [(from: 1, value: 1)],
+                )],
+            )"#,
+        )
+        .unwrap();
+        let mut largest = 0;
+        for seed in 0..10 {
+            let (_, world) = new_game(seed, spawns.clone());
+            let mut packs = std::collections::HashMap::new();
+            for (id, &pack) in world.packs.iter() {
+                packs.entry(pack).or_insert_with(Vec::new).push(id);
+            }
+            assert_eq!(
+                packs.values().map(Vec::len).sum::<usize>(),
+                world.ais.iter().count()
+            );
+            for (first, members) in packs {
+                assert!(members.len() <= 3);
+                largest = largest.max(members.len());
+                for member in members {
+                    let distance = world.objects[member].distance_to(&world.objects[first]);
+                    assert!(distance < 3.0, "seed {}", seed);
+                }
+            }
+        }
+        assert!(largest >= 2);
+    }
+
+    #[test]
+    fn moving_off_the_edge_of_the_map_does_nothing() {
+        let (mut game, mut world) = arena();
+        let player = world.player;
+        for &(x, y, dx, dy) in &[
+            (0, 0, -1, -1),
+            (MAP_WIDTH - 1, 5, 1, 0),
+            (5, MAP_HEIGHT - 1, 0, 1),
+        ] {
+            world.objects[player].set_pos(x, y);
+            move_by(player, dx, dy, &mut game, &mut world);
+            assert_eq!(world.objects[player].pos(), (x, y));
+        }
+    }
+}
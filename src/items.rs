@@ -0,0 +1,561 @@
+use crate::colors::*;
+use crate::effects::{EffectKind, StatusEffect};
+use crate::game::{Game, DROP_COST, PICK_UP_COST, QUAFF_COST, TORCH_RADIUS, USE_ITEM_COST};
+use crate::object::{Ai, Item, Slot};
+use crate::world::{Bundle, Entity, World};
+
+/*********  CONSTANTS  **********/
+/// how many items the player can carry
+pub const INVENTORY_SIZE: usize = 26;
+/// Item settings
+/// how much of the player's maximum hp a healing potion gives back, in percent
+const HEAL_PERCENT: i32 = 40;
+const LIGHTNING_DAMAGE: i32 = 40;
+const LIGHTNING_RANGE: i32 = 5;
+const CONFUSE_RANGE: i32 = 8;
+const CONFUSE_NUM_TURNS: i32 = 10;
+const FIREBALL_RADIUS: i32 = 3;
+const FIREBALL_DAMAGE: i32 = 12;
+const HASTE_TURNS: i32 = 20;
+const STRENGTH_TURNS: i32 = 30;
+const STRENGTH_BONUS: i32 = 3;
+const REGENERATION_TURNS: i32 = 20;
+const REGENERATION_AMOUNT: i32 = 1;
+
+/*********  STRUCTURES  *********/
+/// What happened when an item was used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UseResult {
+    /// it did something, and is gone
+    UsedUp,
+    /// it did something, but is kept (like equipment being put on)
+    UsedAndKept,
+    /// it had nothing to do, so it is kept and no turn is spent
+    Cancelled,
+}
+
+/// What an item has to be pointed at before it can be used
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Targeting {
+    /// nothing, it just works (or finds its own target)
+    None,
+    /// any tile in sight within `range`, hitting everything within `radius` of it
+    Tile { range: i32, radius: i32 },
+    /// a monster in sight within `range`
+    Monster { range: i32 },
+}
+
+/// Where the player pointed an item
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Tile(i32, i32),
+    Monster(Entity),
+}
+
+impl Target {
+    /// the position on the map being targeted
+    pub fn pos(self, world: &World) -> (i32, i32) {
+        match self {
+            Target::Tile(x, y) => (x, y),
+            Target::Monster(id) => world.objects[id].pos(),
+        }
+    }
+
+    /// the monster being targeted, if there is one
+    pub fn monster(self, world: &World) -> Option<Entity> {
+        match self {
+            // it might have died since it was picked
+            Target::Monster(id) => Some(id).filter(|&id| world.contains(id)),
+            Target::Tile(x, y) => world
+                .fighters
+                .entities()
+                .find(|&id| world.objects[id].pos() == (x, y)),
+        }
+    }
+}
+
+impl Item {
+    /// the energy it takes to use
+    pub fn use_cost(self) -> i32 {
+        use Item::*;
+        match self {
+            Heal | Haste | Strength | Regeneration => QUAFF_COST,
+            Lightning | Fireball | Confuse | Sword | Shield => USE_ITEM_COST,
+        }
+    }
+
+    pub fn targeting(self) -> Targeting {
+        use Item::*;
+        match self {
+            Heal | Haste | Strength | Regeneration | Lightning | Sword | Shield => Targeting::None,
+            Fireball => Targeting::Tile {
+                range: TORCH_RADIUS,
+                radius: FIREBALL_RADIUS,
+            },
+            Confuse => Targeting::Monster {
+                range: CONFUSE_RANGE,
+            },
+        }
+    }
+}
+
+/// Add to the player's inventory and remove from the map. Returns whether it
+/// was picked up.
+pub fn pick_item_up(object_id: Entity, game: &mut Game, world: &mut World) -> bool {
+    if game.inventory.len() >= INVENTORY_SIZE {
+        game.messages.add(
+            game.turn,
+            format!(
+                "Your inventory is full, cannot pick up {}.",
+                world.objects[object_id].name
+            ),
+            RED,
+        );
+        false
+    } else {
+        let item = match world.despawn(object_id) {
+            Some(item) => item,
+            None => return false,
+        };
+        world.spend_energy(world.player, PICK_UP_COST);
+        game.messages.add(
+            game.turn,
+            format!("You picked up a {}!", item.object.name),
+            GREEN,
+        );
+        game.inventory.push(item);
+        true
+    }
+}
+
+/// remove from the player's inventory and put it back on the map under the player
+pub fn drop_item(inventory_id: usize, game: &mut Game, world: &mut World) {
+    // take it off first, if it's being worn
+    dequip(inventory_id, game, world);
+    let mut item = game.inventory.remove(inventory_id);
+    let (x, y) = world.objects[world.player].pos();
+    item.object.set_pos(x, y);
+    game.messages.add(
+        game.turn,
+        format!("You dropped a {}.", item.object.name),
+        YELLOW,
+    );
+    world.spawn(item);
+    world.spend_energy(world.player, DROP_COST);
+}
+
+/// the id of an item lying under the player, if there is one
+pub fn item_under_player(world: &World) -> Option<Entity> {
+    world
+        .items
+        .entities()
+        .find(|&id| world.objects[id].pos() == world.objects[world.player].pos())
+}
+
+/// Use an item from the inventory, pointed at `target` if it needs one (see
+/// `Item::targeting`). The item is only used up if it actually did something.
+pub fn use_item(
+    inventory_id: usize,
+    target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    use Item::*;
+    // just call the "use_function" if it is defined
+    if let Some(item) = game.inventory[inventory_id].item {
+        let on_use: fn(usize, Option<Target>, &mut Game, &mut World) -> UseResult = match item {
+            Heal => cast_heal,
+            Haste => drink_haste,
+            Strength => drink_strength,
+            Regeneration => drink_regeneration,
+            Lightning => cast_lightning,
+            Fireball => cast_fireball,
+            Confuse => cast_confuse,
+            Sword | Shield => toggle_equipment,
+        };
+        let result = on_use(inventory_id, target, game, world);
+        if result != UseResult::Cancelled {
+            world.spend_energy(world.player, item.use_cost());
+        }
+        match result {
+            UseResult::UsedUp => {
+                // destroy after use, unless it was cancelled for some reason
+                game.inventory.remove(inventory_id);
+                UseResult::UsedUp
+            }
+            UseResult::UsedAndKept => UseResult::UsedAndKept,
+            UseResult::Cancelled => {
+                game.messages.add(game.turn, "Cancelled", WHITE);
+                UseResult::Cancelled
+            }
+        }
+    } else {
+        game.messages.add(
+            game.turn,
+            format!(
+                "The {} cannot be used.",
+                game.inventory[inventory_id].object.name
+            ),
+            WHITE,
+        );
+        UseResult::Cancelled
+    }
+}
+
+/// find the closest enemy the player can see, up to a maximum range
+pub fn closest_monster(max_range: i32, game: &Game, world: &World) -> Option<Entity> {
+    let mut closest_enemy = None;
+    let mut closest_dist = (max_range + 1) as f32; // start with (slightly more than) maximum range
+
+    for id in world.ais.entities() {
+        let object = &world.objects[id];
+        if (id != world.player) && world.fighters.has(id) && game.fov.is_in_fov(object.x, object.y)
+        {
+            // calculate distance between this object and the player
+            let dist = world.objects[world.player].distance_to(object);
+            if dist < closest_dist {
+                // it's closer, so remember it
+                closest_enemy = Some(id);
+                closest_dist = dist;
+            }
+        }
+    }
+    closest_enemy
+}
+
+/// Turn a tile the player pointed at into a target for an item, if the item can
+/// be used there: the tile has to be in sight and within the item's range, and
+/// hold a monster if the item needs one
+pub fn target_at(
+    targeting: Targeting,
+    (x, y): (i32, i32),
+    game: &Game,
+    world: &World,
+) -> Option<Target> {
+    let in_range = |range: i32| {
+        game.fov.is_in_fov(x, y) && world.objects[world.player].distance((x, y)) <= range as f32
+    };
+    match targeting {
+        Targeting::Tile { range, .. } if in_range(range) => Some(Target::Tile(x, y)),
+        Targeting::Monster { range } if in_range(range) => world
+            .fighters
+            .entities()
+            .find(|&id| id != world.player && world.objects[id].pos() == (x, y))
+            .map(Target::Monster),
+        _ => None,
+    }
+}
+
+/// heal the player
+fn cast_heal(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    if let Some(hp) = world.fighters.get(world.player).map(|f| f.hp) {
+        if hp == world.max_hp(world.player, game) {
+            game.messages
+                .add(game.turn, "You are already at full health.", RED);
+            return UseResult::Cancelled;
+        }
+        game.messages
+            .add(game.turn, "Your wounds start to feel better!", LIGHT_VIOLET);
+        let amount = world.max_hp(world.player, game) * HEAL_PERCENT / 100;
+        world.heal(world.player, amount, game);
+        return UseResult::UsedUp;
+    }
+    UseResult::Cancelled
+}
+
+/// speed the player up
+fn drink_haste(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let effect = StatusEffect::new(EffectKind::Haste, HASTE_TURNS, 0);
+    drink(effect, "You feel yourself speed up!", game, world)
+}
+
+/// make the player hit harder for a while
+fn drink_strength(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let effect = StatusEffect::new(EffectKind::Strength, STRENGTH_TURNS, STRENGTH_BONUS);
+    drink(effect, "Your muscles swell with strength!", game, world)
+}
+
+/// heal the player a little every turn for a while
+fn drink_regeneration(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let effect = StatusEffect::new(
+        EffectKind::Regeneration,
+        REGENERATION_TURNS,
+        REGENERATION_AMOUNT,
+    );
+    drink(effect, "A warm glow spreads through you.", game, world)
+}
+
+/// put a status effect from a potion on the player
+fn drink(effect: StatusEffect, message: &str, game: &mut Game, world: &mut World) -> UseResult {
+    game.messages.add(game.turn, message, effect.kind.color());
+    world.add_effect(world.player, effect);
+    UseResult::UsedUp
+}
+
+/// find the closest enemy (inside a maximum range) and damage it
+fn cast_lightning(
+    _inventory_id: usize,
+    _target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let monster_id = closest_monster(LIGHTNING_RANGE, game, world);
+    if let Some(monster_id) = monster_id {
+        // zap it!
+        game.messages.add(
+            game.turn,
+            format!(
+                "A lightning bolt strikes the {} with a loud thunder! \
+                 The damage is {} hit points.",
+                world.objects[monster_id].name, LIGHTNING_DAMAGE
+            ),
+            LIGHT_BLUE,
+        );
+        if let Some(xp) = world.take_damage(monster_id, LIGHTNING_DAMAGE, game) {
+            world.gain_xp(world.player, xp);
+        }
+        UseResult::UsedUp
+    } else {
+        // no enemy found within maximum range
+        game.messages
+            .add(game.turn, "No enemy is close enough to strike.", RED);
+        UseResult::Cancelled
+    }
+}
+
+/// damage every fighter within a radius of the target tile, the player included
+fn cast_fireball(
+    _inventory_id: usize,
+    target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let (x, y) = match target.map(|target| target.pos(world)) {
+        Some(pos) => pos,
+        None => return UseResult::Cancelled,
+    };
+    game.messages.add(
+        game.turn,
+        format!(
+            "The fireball explodes, burning everything within {} tiles!",
+            FIREBALL_RADIUS
+        ),
+        ORANGE,
+    );
+
+    let mut xp_to_gain = 0;
+    let in_blast: Vec<_> = world
+        .fighters
+        .entities()
+        .filter(|&id| world.objects[id].distance((x, y)) <= FIREBALL_RADIUS as f32)
+        .collect();
+    for id in in_blast {
+        game.messages.add(
+            game.turn,
+            format!(
+                "The {} gets burned for {} hit points.",
+                world.objects[id].name, FIREBALL_DAMAGE
+            ),
+            ORANGE,
+        );
+        if let Some(xp) = world.take_damage(id, FIREBALL_DAMAGE, game) {
+            // don't reward the player for burning themself!
+            if id != world.player {
+                xp_to_gain += xp;
+            }
+        }
+    }
+    world.gain_xp(world.player, xp_to_gain);
+
+    UseResult::UsedUp
+}
+
+/// confuse the targeted monster, replacing its AI for a while
+fn cast_confuse(
+    _inventory_id: usize,
+    target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let monster_id = target.and_then(|target| target.monster(world));
+    let monster_id = match monster_id {
+        Some(id) if id != world.player && world.ais.has(id) => id,
+        _ => {
+            game.messages
+                .add(game.turn, "There is no enemy there to confuse.", RED);
+            return UseResult::Cancelled;
+        }
+    };
+    if world.objects[world.player].distance_to(&world.objects[monster_id]) > CONFUSE_RANGE as f32 {
+        game.messages
+            .add(game.turn, "That enemy is too far away to confuse.", RED);
+        return UseResult::Cancelled;
+    }
+
+    // replace the monster's AI with a "confused" one; after some turns it will
+    // restore the old AI. Confusing it again just starts the count over.
+    let old_ai = match world.ais.remove(monster_id) {
+        Some(Ai::Confused { previous_ai, .. }) => *previous_ai,
+        Some(ai) => ai,
+        None => Ai::Basic,
+    };
+    world.insert(
+        monster_id,
+        |w| &mut w.ais,
+        Ai::Confused {
+            previous_ai: Box::new(old_ai),
+            turns: CONFUSE_NUM_TURNS,
+        },
+    );
+    game.messages.add(
+        game.turn,
+        format!(
+            "The eyes of {} look vacant, as he starts to stumble around!",
+            world.objects[monster_id].name
+        ),
+        LIGHT_GREEN,
+    );
+    UseResult::UsedUp
+}
+
+/// put a piece of equipment on, or take it off if it already is
+fn toggle_equipment(
+    inventory_id: usize,
+    _target: Option<Target>,
+    game: &mut Game,
+    world: &mut World,
+) -> UseResult {
+    let equipment = match game.inventory[inventory_id].equipment {
+        Some(equipment) => equipment,
+        None => return UseResult::Cancelled,
+    };
+    if equipment.equipped {
+        dequip(inventory_id, game, world);
+    } else {
+        // if the slot is already being used, dequip whatever is there first
+        if let Some(current) = get_equipped_in_slot(equipment.slot, &game.inventory) {
+            dequip(current, game, world);
+        }
+        equip(inventory_id, game);
+    }
+    UseResult::UsedAndKept
+}
+
+/// Equip object and show a message about it
+pub fn equip(inventory_id: usize, game: &mut Game) {
+    let item = &mut game.inventory[inventory_id];
+    if let Some(ref mut equipment) = item.equipment {
+        if !equipment.equipped {
+            equipment.equipped = true;
+            let message = format!("Equipped {} on {}.", item.object.name, equipment.slot);
+            game.messages.add(game.turn, message, LIGHT_GREEN);
+        }
+    }
+}
+
+/// Dequip object and show a message about it. Whatever hit points it gave
+/// that the player no longer has room for are lost with it.
+pub fn dequip(inventory_id: usize, game: &mut Game, world: &mut World) {
+    let item = &mut game.inventory[inventory_id];
+    if let Some(ref mut equipment) = item.equipment {
+        if equipment.equipped {
+            equipment.equipped = false;
+            let message = format!("Dequipped {} from {}.", item.object.name, equipment.slot);
+            game.messages.add(game.turn, message, LIGHT_YELLOW);
+        }
+    }
+    let max_hp = world.max_hp(world.player, game);
+    if let Some(fighter) = world.fighters.get_mut(world.player) {
+        fighter.hp = fighter.hp.min(max_hp);
+    }
+}
+
+/// the inventory index of whatever is equipped in a slot, if anything
+pub fn get_equipped_in_slot(slot: Slot, inventory: &[Bundle]) -> Option<usize> {
+    inventory
+        .iter()
+        .position(|item| item.equipment.is_some_and(|e| e.equipped && e.slot == slot))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::new_game;
+    use crate::object::{Equipment, Object};
+    use crate::spawns::SpawnTable;
+
+    /// an amulet worn on the head that adds `max_hp_bonus`, in the inventory
+    fn carry_amulet(max_hp_bonus: i32, game: &mut Game) -> usize {
+        let mut amulet = Bundle::new(Object::new(0, 0, '"', YELLOW, false, "amulet"));
+        amulet.equipment = Some(Equipment {
+            slot: Slot::Head,
+            equipped: false,
+            power_bonus: 0,
+            defense_bonus: 0,
+            max_hp_bonus,
+        });
+        game.inventory.push(amulet);
+        game.inventory.len() - 1
+    }
+
+    #[test]
+    fn healing_potions_restore_a_share_of_max_hp() {
+        let (mut game, mut world) = new_game(1, SpawnTable::default());
+        let player = world.player;
+        let mut potion = Bundle::new(Object::new(0, 0, '!', VIOLET, false, "healing potion"));
+        potion.item = Some(Item::Heal);
+        game.inventory.push(potion);
+        world.fighters[player].hp = 5;
+
+        assert_eq!(use_item(0, None, &mut game, &mut world), UseResult::UsedUp);
+        // 40% of 30
+        assert_eq!(world.fighters[player].hp, 17);
+        assert!(game.inventory.is_empty());
+    }
+
+    #[test]
+    fn taking_off_max_hp_gear_caps_hp() {
+        let (mut game, mut world) = new_game(1, SpawnTable::default());
+        let player = world.player;
+        let amulet = carry_amulet(10, &mut game);
+        equip(amulet, &mut game);
+        world.heal(player, 100, &game);
+        assert_eq!(world.fighters[player].hp, 40);
+
+        dequip(amulet, &mut game, &mut world);
+        assert_eq!(world.max_hp(player, &game), 30);
+        assert_eq!(world.fighters[player].hp, 30);
+    }
+
+    #[test]
+    fn taking_off_gear_keeps_damage_taken() {
+        let (mut game, mut world) = new_game(1, SpawnTable::default());
+        let player = world.player;
+        let amulet = carry_amulet(10, &mut game);
+        equip(amulet, &mut game);
+        world.fighters[player].hp = 12;
+
+        dequip(amulet, &mut game, &mut world);
+        assert_eq!(world.fighters[player].hp, 12);
+    }
+}
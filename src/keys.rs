@@ -0,0 +1,358 @@
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/*********  CONSTANTS  **********/
+/// The key bindings the game ships with. Anything a config file leaves out
+/// keeps the binding from here.
+const DEFAULT_BINDINGS: &str = include_str!("../data/keybindings.ron");
+
+/// every command, by the name it has in a key bindings file
+const COMMANDS: &[(&str, Command)] = &[
+    ("move_north", Command::Move(0, -1)),
+    ("move_south", Command::Move(0, 1)),
+    ("move_west", Command::Move(-1, 0)),
+    ("move_east", Command::Move(1, 0)),
+    ("move_north_west", Command::Move(-1, -1)),
+    ("move_north_east", Command::Move(1, -1)),
+    ("move_south_west", Command::Move(-1, 1)),
+    ("move_south_east", Command::Move(1, 1)),
+    ("wait", Command::Wait),
+    ("search", Command::Search),
+    ("pick_up", Command::PickUp),
+    ("descend", Command::Descend),
+    ("inventory", Command::Inventory),
+    ("message_log", Command::MessageLog),
+    ("exit", Command::Exit),
+];
+
+/*********  STRUCTURES  *********/
+/// Something the player can ask for with a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// move, or attack whatever is in the way
+    Move(i32, i32),
+    /// let a turn go by
+    Wait,
+    /// look for secret doors
+    Search,
+    PickUp,
+    /// take the stairs down
+    Descend,
+    Inventory,
+    MessageLog,
+    Exit,
+}
+
+/// A key, independent of whichever library reads the keyboard
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Keypress {
+    /// a key that types a character, like `k` or `>`
+    Char(char),
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Escape,
+    Enter,
+    /// one of the digits on the number pad
+    NumPad(u8),
+}
+
+/// Which key does what
+#[derive(Clone, Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<Keypress, Command>,
+}
+
+/// The commands in a bindings file and their keys, in the order they are
+/// written, so that the first of two conflicting commands is the one kept
+struct BindingsFile(Vec<(String, Vec<String>)>);
+
+impl Keypress {
+    /// read a key from how it's written in a bindings file: a single
+    /// character, or a name like `Up` or `NumPad7`
+    pub fn parse(name: &str) -> Option<Keypress> {
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Some(Keypress::Char(c));
+        }
+        let key = match name {
+            "Up" => Keypress::Up,
+            "Down" => Keypress::Down,
+            "Left" => Keypress::Left,
+            "Right" => Keypress::Right,
+            "Home" => Keypress::Home,
+            "End" => Keypress::End,
+            "PageUp" => Keypress::PageUp,
+            "PageDown" => Keypress::PageDown,
+            "Escape" => Keypress::Escape,
+            "Enter" => Keypress::Enter,
+            _ => {
+                let digit = name.strip_prefix("NumPad")?.parse().ok()?;
+                if digit > 9 {
+                    return None;
+                }
+                Keypress::NumPad(digit)
+            }
+        };
+        Some(key)
+    }
+}
+
+impl fmt::Display for Keypress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Keypress::Char(c) => write!(f, "'{}'", c),
+            Keypress::NumPad(digit) => write!(f, "NumPad{}", digit),
+            ref key => write!(f, "{:?}", key),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Read key bindings from a RON file, mapping command names to lists of
+    /// keys. Nothing in it is fatal: a missing file means the default
+    /// bindings, and everything wrong with it comes back as a warning.
+    pub fn load(path: &Path) -> (Self, Vec<String>) {
+        match fs::read_to_string(path) {
+            Ok(data) => KeyBindings::parse(&data),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                (KeyBindings::default(), vec![])
+            }
+            Err(err) => (
+                KeyBindings::default(),
+                vec![format!(
+                    "could not read {}, using the default keys: {}",
+                    path.display(),
+                    err
+                )],
+            ),
+        }
+    }
+
+    /// Build the bindings from the text of a bindings file, on top of the
+    /// defaults. Returns them along with warnings about anything that was
+    /// skipped.
+    pub fn parse(data: &str) -> (Self, Vec<String>) {
+        let mut warnings = vec![];
+        let file = match ron::from_str::<BindingsFile>(data) {
+            Ok(BindingsFile(file)) => file,
+            Err(err) => {
+                warnings.push(format!(
+                    "the key bindings could not be parsed, using the default keys: {}",
+                    err
+                ));
+                return (KeyBindings::default(), warnings);
+            }
+        };
+        let defaults = default_table();
+
+        // the commands in the file come first, so they win any conflict with
+        // a default binding
+        let mut table: Vec<(&str, Command, &Vec<String>)> = vec![];
+        for (name, keys) in &file {
+            if table.iter().any(|&(other, _, _)| other == name) {
+                warnings.push(format!(
+                    "\"{}\" is listed more than once; keeping the first",
+                    name
+                ));
+                continue;
+            }
+            match command_named(name) {
+                Some(command) => table.push((name.as_str(), command, keys)),
+                None => warnings.push(format!("unknown command \"{}\" ignored", name)),
+            }
+        }
+        for (name, keys) in &defaults {
+            if !file.iter().any(|(other, _)| other == name) {
+                if let Some(command) = command_named(name) {
+                    table.push((name.as_str(), command, keys));
+                }
+            }
+        }
+
+        let mut bindings = HashMap::new();
+        let mut bound_by: HashMap<Keypress, &str> = HashMap::new();
+        for (name, command, keys) in table {
+            for key_name in keys {
+                let key = match Keypress::parse(key_name) {
+                    Some(key) => key,
+                    None => {
+                        warnings.push(format!(
+                            "unknown key \"{}\" for \"{}\" ignored",
+                            key_name, name
+                        ));
+                        continue;
+                    }
+                };
+                match bound_by.get(&key) {
+                    Some(&other) if other != name => warnings.push(format!(
+                        "{} is bound to both \"{}\" and \"{}\"; keeping \"{}\"",
+                        key, other, name, other
+                    )),
+                    Some(_) => {}
+                    None => {
+                        bound_by.insert(key, name);
+                        bindings.insert(key, command);
+                    }
+                }
+            }
+        }
+        (KeyBindings { bindings }, warnings)
+    }
+
+    /// what a key does, if anything
+    pub fn command(&self, key: Keypress) -> Option<Command> {
+        self.bindings.get(&key).copied()
+    }
+}
+
+impl Default for KeyBindings {
+    /// the bindings from `data/keybindings.ron`, as it was when the game was built
+    fn default() -> Self {
+        let mut bindings = HashMap::new();
+        for (name, keys) in default_table() {
+            let command = command_named(&name).expect("unknown command in the default keys");
+            for key in keys {
+                let key = Keypress::parse(&key).expect("unknown key in the default keys");
+                bindings.entry(key).or_insert(command);
+            }
+        }
+        KeyBindings { bindings }
+    }
+}
+
+impl<'de> Deserialize<'de> for BindingsFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FileVisitor;
+
+        impl<'de> Visitor<'de> for FileVisitor {
+            type Value = BindingsFile;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of command names to lists of keys")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<BindingsFile, A::Error> {
+                let mut commands = vec![];
+                while let Some(entry) = map.next_entry()? {
+                    commands.push(entry);
+                }
+                Ok(BindingsFile(commands))
+            }
+        }
+
+        deserializer.deserialize_map(FileVisitor)
+    }
+}
+
+/// the default bindings, as they're written in the file
+fn default_table() -> Vec<(String, Vec<String>)> {
+    ron::from_str::<BindingsFile>(DEFAULT_BINDINGS)
+        .expect("the default key bindings are invalid")
+        .0
+}
+
+fn command_named(name: &str) -> Option<Command> {
+    COMMANDS
+        .iter()
+        .find(|&&(command_name, _)| command_name == name)
+        .map(|&(_, command)| command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_default_bindings_have_no_warnings() {
+        let (keys, warnings) = KeyBindings::parse(DEFAULT_BINDINGS);
+        assert_eq!(warnings, Vec::<String>::new());
+        assert_eq!(
+            keys.command(Keypress::Char('k')),
+            Some(Command::Move(0, -1))
+        );
+        assert_eq!(keys.command(Keypress::NumPad(5)), Some(Command::Wait));
+    }
+
+    #[test]
+    fn bindings_in_the_file_replace_the_defaults() {
+        let (keys, warnings) = KeyBindings::parse(r#"{ "search": ["S"] }"#);
+        assert!(warnings.is_empty(), "{:?}", warnings);
+        assert_eq!(keys.command(Keypress::Char('S')), Some(Command::Search));
+        assert_eq!(keys.command(Keypress::Char('s')), None);
+        // and what the file leaves out keeps its default keys
+        assert_eq!(keys.command(Keypress::Char('g')), Some(Command::PickUp));
+    }
+
+    #[test]
+    fn unknown_commands_are_ignored() {
+        let (keys, warnings) = KeyBindings::parse(r#"{ "fly": ["f"], "wait": ["w"] }"#);
+        assert_eq!(warnings, vec!["unknown command \"fly\" ignored"]);
+        assert_eq!(keys.command(Keypress::Char('f')), None);
+        assert_eq!(keys.command(Keypress::Char('w')), Some(Command::Wait));
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let (keys, warnings) = KeyBindings::parse(r#"{ "wait": ["Space", "NumPad10", "w"] }"#);
+        assert_eq!(
+            warnings,
+            vec![
+                "unknown key \"Space\" for \"wait\" ignored",
+                "unknown key \"NumPad10\" for \"wait\" ignored",
+            ]
+        );
+        assert_eq!(keys.command(Keypress::Char('w')), Some(Command::Wait));
+    }
+
+    #[test]
+    fn the_first_of_two_conflicting_commands_wins() {
+        // "wait" sorts after "search", but comes first in the file
+        let (keys, warnings) = KeyBindings::parse(r#"{ "wait": ["x"], "search": ["x"] }"#);
+        assert_eq!(
+            warnings,
+            vec!["'x' is bound to both \"wait\" and \"search\"; keeping \"wait\""]
+        );
+        assert_eq!(keys.command(Keypress::Char('x')), Some(Command::Wait));
+
+        // and the file wins over the defaults
+        let (keys, warnings) = KeyBindings::parse(r#"{ "exit": ["k"] }"#);
+        assert_eq!(
+            warnings,
+            vec!["'k' is bound to both \"exit\" and \"move_north\"; keeping \"exit\""]
+        );
+        assert_eq!(keys.command(Keypress::Char('k')), Some(Command::Exit));
+    }
+
+    #[test]
+    fn commands_listed_twice_keep_the_first_keys() {
+        let (keys, warnings) = KeyBindings::parse(r#"{ "wait": ["w"], "wait": ["x"] }"#);
+        assert_eq!(
+            warnings,
+            vec!["\"wait\" is listed more than once; keeping the first"]
+        );
+        assert_eq!(keys.command(Keypress::Char('w')), Some(Command::Wait));
+        assert_eq!(keys.command(Keypress::Char('x')), None);
+    }
+
+    #[test]
+    fn a_broken_file_means_the_default_keys() {
+        let (keys, warnings) = KeyBindings::parse(r#"{ "wait": "w" "#);
+        assert_eq!(warnings.len(), 1);
+        assert!(
+            warnings[0].starts_with("the key bindings could not be parsed"),
+            "{}",
+            warnings[0]
+        );
+        assert_eq!(keys.command(Keypress::Char('.')), Some(Command::Wait));
+    }
+}
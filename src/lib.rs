@@ -0,0 +1,27 @@
+//! The game core: map generation, objects, combat, AI and the turn loop, and
+//! the screens that show it.
+//!
+//! Nothing in here depends on a particular library to draw the game or read
+//! input: the screens in `ui` go through the `Console` and `Input` traits, so
+//! the rules can be driven and tested without opening a window. The frontends
+//! are the binaries: a tcod window in `main.rs`, and a truecolor terminal in
+//! `bin/terminal.rs`.
+
+pub mod buffer;
+pub mod camera;
+pub mod colors;
+pub mod console;
+pub mod effects;
+pub mod fov;
+pub mod game;
+pub mod items;
+pub mod keys;
+pub mod map;
+pub mod mapgen;
+pub mod messages;
+pub mod object;
+pub mod path;
+pub mod save;
+pub mod spawns;
+pub mod ui;
+pub mod world;
@@ -1,125 +1,21 @@
-use rand::Rng;
-use std::cmp;
-use tcod::colors::*;
-use tcod::console::*;
-use tcod::map::{FovAlgorithm, Map as FovMap};
+use tcod::colors::Color;
+use tcod::console::{BackgroundFlag, Console as _, FontLayout, FontType, Root};
+use tcod::input::{self, Key};
 
-/*********  CONSTANTS  **********/
-/// Player
-const PLAYER: usize = 0;
-/// Actual size of the window
-const SCREEN_WIDTH: i32 = 80;
-const SCREEN_HEIGHT: i32 = 50;
-/// size of the map
-const MAP_WIDTH: i32 = 80;
-const MAP_HEIGHT: i32 = 45;
-/// Other settings
-const LIMIT_FPS: i32 = 20; // 20 frames-per-second maximum
-/// Room settings
-const ROOM_MAX_SIZE: i32 = 10;
-const ROOM_MIN_SIZE: i32 = 6;
-const MAX_ROOMS: i32 = 30;
-// Monster Settings
-const MAX_ROOM_MONSTERS: i32 = 3;
-// Lighting and FOV
-const FOV_ALGO: FovAlgorithm = FovAlgorithm::Basic; // default FOV algorithm
-const FOV_LIGHT_WALLS: bool = true; // light walls or not
-const TORCH_RADIUS: i32 = 10;
-/// Colors
-const COLOR_DARK_WALL: Color = Color { r: 0, g: 0, b: 100 };
-const COLOR_LIGHT_WALL: Color = Color {
-    r: 130,
-    g: 110,
-    b: 50,
-};
-const COLOR_DARK_GROUND: Color = Color {
-    r: 50,
-    g: 50,
-    b: 150,
-};
-const COLOR_LIGHT_GROUND: Color = Color {
-    r: 200,
-    g: 180,
-    b: 50,
-};
+use roguelike::colors as palette;
+use roguelike::console::{Console, Event, Input, Mouse};
+use roguelike::keys::Keypress;
+use roguelike::ui::{self, Ui, LIMIT_FPS, SCREEN_HEIGHT, SCREEN_WIDTH};
 
 /*********  STRUCTURES  *********/
+/// The game in a libtcod window
 struct Tcod {
     root: Root,
-    con: Offscreen,
-    fov: FovMap,
-}
-
-/// This is a generic object: the player, a monster, an item, the stairs...
-/// It's always represented by a charactor on the screen.
-#[derive(Debug)]
-struct Object {
-    x: i32,
-    y: i32,
-    char: char,
-    color: Color,
-    name: String,
-    blocks: bool,
-    alive: bool,
-    fighter: Option<Fighter>,
-    ai: Option<Ai>,
-}
-
-/// combat-related properties and methods (monster, player, NPC).
-#[derive(Clone, Copy, Debug, PartialEq)]
-struct Fighter {
-    max_hp: i32,
-    hp: i32,
-    defense: i32,
-    power: i32,
-    on_death: DeathCallback,
-}
-
-/// A tile of the map and it's prooerties
-#[derive(Clone, Copy, Debug)]
-struct Tile {
-    blocked: bool,
-    explored: bool,
-    block_sight: bool,
-}
-
-/// a list of list of tiles, to represent the map
-type Map = Vec<Vec<Tile>>;
-
-struct Game {
-    map: Map,
-}
-
-/// A rectangle on the map, used to characterise a room.
-#[derive(Clone, Copy, Debug)]
-struct Rect {
-    x1: i32,
-    y1: i32,
-    x2: i32,
-    y2: i32,
-}
-
-/// Options for what the player did this frame
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum PlayerAction {
-    TookTurn,
-    DidntTakeTurn,
-    Exit,
-}
-
-/// Options for AI
-#[derive(Clone, Debug, PartialEq)]
-enum Ai {
-    Basic,
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum DeathCallback {
-    Player,
-    Monster,
 }
 
 fn main() {
+    let setup = ui::setup(std::env::args().skip(1));
+
     tcod::system::set_fps(LIMIT_FPS);
 
     let root = Root::initializer()
@@ -129,556 +25,134 @@ fn main() {
         .title("Rust/libtcod tutorial")
         .init();
 
-    let mut tcod = Tcod {
-        root,
-        con: Offscreen::new(SCREEN_WIDTH, SCREEN_HEIGHT),
-        fov: FovMap::new(MAP_WIDTH, MAP_HEIGHT),
-    };
-
-    // Initilize Player Properties
-    let mut player = Object::new(0, 0, '@', WHITE, true, "player");
-    player.alive = true;
-    player.fighter = Some(Fighter {
-        max_hp: 30,
-        hp: 30,
-        defense: 2,
-        power: 5,
-        on_death: DeathCallback::Player,
-    });
-
-    // List of objects in the game, currently player, npc
-    let mut objects = vec![player];
-    let mut game = Game {
-        map: make_map(&mut objects),
-    };
-
-    // populate the FOV map, according to the generated map
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            tcod.fov.set(
-                x,
-                y,
-                !game.map[x as usize][y as usize].block_sight,
-                !game.map[x as usize][y as usize].blocked,
-            );
-        }
-    }
-    // force FOV "recompute" first time through the game loop
-    let mut previous_player_position = (-1, -1);
-
-    while !tcod.root.window_closed() {
-        // clear the screen of the previous frame
-        tcod.con.clear();
-        // handle the updating of the view port
-        // render the screen
-        let fov_recompute = previous_player_position != objects[PLAYER].pos();
-        render_all(&mut tcod, &mut game, &objects, fov_recompute);
-
-        tcod.root.flush();
-
-        // Key handleing w/ exit
-        let player = &mut objects[PLAYER];
-        previous_player_position = (player.x, player.y);
-        let player_action = handle_keys(&mut tcod, &mut objects, &game);
-        if player_action == PlayerAction::Exit {
-            break;
-        }
-
-        // Let the monsters take their turn
-        if objects[PLAYER].alive && player_action != PlayerAction::DidntTakeTurn {
-            for id in 0..objects.len() {
-                // only if object is not a player
-                if objects[id].ai.is_some() {
-                    ai_take_turn(id, &tcod, &game, &mut objects);
-                }
-            }
-        }
-    }
+    let mut ui = Ui::new(Tcod { root }, setup.keys);
+    ui::main_menu(&mut ui, setup.seed, &setup.spawns);
 }
 
-/// Fucntions for the object structure
-impl Object {
-    pub fn new(x: i32, y: i32, char: char, color: Color, blocks: bool, name: &str) -> Self {
-        Object {
-            x,
-            y,
-            char,
-            color,
-            name: name.into(),
-            blocks,
-            alive: false,
-            fighter: None,
-            ai: None,
-        }
-    }
-
-    /// set the color and then draw the character that represents this object at it's
-    /// position on the screen
-    pub fn draw(&self, con: &mut dyn Console) {
-        con.set_default_foreground(self.color);
-        con.put_char(self.x, self.y, self.char, BackgroundFlag::None);
-    }
-
-    /// return the position of the object
-    pub fn pos(&self) -> (i32, i32) {
-        (self.x, self.y)
-    }
-
-    /// set the position of the object
-    pub fn set_pos(&mut self, x: i32, y: i32) {
-        self.x = x;
-        self.y = y;
-    }
-
-    /// return the distance to another object
-    pub fn distance_to(&self, other: &Object) -> f32 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
-    }
-
-    /// take a specified amount of damage
-    pub fn take_damage(&mut self, damage: i32) {
-        // apply damage if possible
-        if let Some(fighter) = self.fighter.as_mut() {
-            if damage > 0 {
-                fighter.hp -= damage;
-            }
-        }
-        // check for death, call the death function
-        if let Some(fighter) = self.fighter {
-            if fighter.hp <= 0 {
-                self.alive = false;
-                fighter.on_death.callback(self);
-            }
-        }
+impl Tcod {
+    fn in_bounds(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.root.width() && y < self.root.height()
     }
 
-    pub fn attack(&mut self, target: &mut Object) {
-        // a simple formula for the attack damage
-        let damage = self.fighter.map_or(0, |f| f.power) - target.fighter.map_or(0, |f| f.defense);
-        if damage > 0 {
-            // make the damage take some damage
-            println!(
-                "{} attacks {} for {} hit points.",
-                self.name, target.name, damage
-            );
-            target.take_damage(damage);
+    /// Alt + Enter: toggle fullscreen. Returns whether that's what `key` was.
+    fn toggle_fullscreen(&mut self, key: Key) -> bool {
+        if key.code == input::KeyCode::Enter && key.alt {
+            let fullscreen = self.root.is_fullscreen();
+            self.root.set_fullscreen(!fullscreen);
+            true
         } else {
-            println!(
-                "{} attacks {} but it has no effect!",
-                self.name, target.name
-            );
-        }
-    }
-}
-
-/// Functions for the tile structure
-impl Tile {
-    pub fn empty() -> Self {
-        Tile {
-            blocked: false,
-            explored: false,
-            block_sight: false,
+            false
         }
     }
-
-    pub fn wall() -> Self {
-        Tile {
-            blocked: true,
-            explored: false,
-            block_sight: true,
-        }
-    }
-}
-
-/// Functinos for the rectangle structure
-impl Rect {
-    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
-        Rect {
-            x1: x,
-            x2: x + w,
-            y1: y,
-            y2: y + h,
-        }
-    }
-
-    pub fn center(&self) -> (i32, i32) {
-        let center_x = (self.x1 + self.x2) / 2;
-        let center_y = (self.y1 + self.y2) / 2;
-        (center_x, center_y)
-    }
-
-    pub fn intersects_with(&self, other: &Rect) -> bool {
-        // returns tru if this rectangle intersects with another one
-        (self.x1 <= other.x2)
-            && (self.x2 > other.x1)
-            && (self.y1 <= other.y2)
-            && (self.y2 >= other.y1)
-    }
 }
 
-impl DeathCallback {
-    fn callback(self, object: &mut Object) {
-        use DeathCallback::*;
-        let callback: fn(&mut Object) = match self {
-            Player => player_death,
-            Monster => monster_death,
-        };
-        callback(object);
+impl Console for Tcod {
+    fn size(&self) -> (i32, i32) {
+        (self.root.width(), self.root.height())
     }
-}
 
-/********** GENERIC FUNCTIONS ***********/
-
-/// move by the given amount, if the destination is not blocked
-fn move_by(id: usize, dx: i32, dy: i32, map: &Map, objects: &mut [Object]) {
-    let (x, y) = objects[id].pos();
-    if !is_blocked(x + dx, y + dy, map, objects) {
-        objects[id].set_pos(x + dx, y + dy);
+    fn clear(&mut self) {
+        self.root.set_default_background(tcod::colors::BLACK);
+        self.root.clear();
     }
-}
-
-/// move the player and potentially attack a target
-fn player_move_or_attack(dx: i32, dy: i32, game: &Game, objects: &mut [Object]) {
-    // the coordinates the player is moving to/attacking
-    let x = objects[PLAYER].x + dx;
-    let y = objects[PLAYER].y + dy;
-
-    // try to find an attackable target there
-    let target_id = objects
-        .iter()
-        .position(|object| object.fighter.is_some() && object.pos() == (x, y));
 
-    // attack if target found, move otherwise
-    match target_id {
-        Some(target_id) => {
-            let (player, target) = mut_two(PLAYER, target_id, objects);
-            player.attack(target);
+    fn put_char(&mut self, x: i32, y: i32, glyph: char, color: palette::Color) {
+        if self.in_bounds(x, y) {
+            self.root.set_char(x, y, glyph);
+            self.root.set_char_foreground(x, y, tcod_color(color));
         }
-        None => move_by(PLAYER, dx, dy, &game.map, objects),
     }
-}
-
-/// move an object to move towards a position
-fn move_towards(id: usize, target_x: i32, target_y: i32, map: &Map, objects: &mut [Object]) {
-    // vector from this object to the target and distance
-    let dx = target_x - objects[id].x;
-    let dy = target_y - objects[id].y;
-    let distance = ((dx.pow(2) + dy.pow(2)) as f32).sqrt();
-
-    // normalize it to length 1 (preserving direction), then round it and convert it to
-    // integer so the movement is restricted to the map grid
-    let dx = (dx as f32 / distance).round() as i32;
-    let dy = (dy as f32 / distance).round() as i32;
-    move_by(id, dx, dy, map, objects);
-}
 
-/// move a single monster
-fn ai_take_turn(monster_id: usize, tcod: &Tcod, game: &Game, objects: &mut [Object]) {
-    // a basic monster takes its turn.  If you can see it, it can see you
-    let (monster_x, monster_y) = objects[monster_id].pos();
-    if tcod.fov.is_in_fov(monster_x, monster_y) {
-        if objects[monster_id].distance_to(&objects[PLAYER]) >= 2.0 {
-            // move towards the player if far away
-            let (player_x, player_y) = objects[PLAYER].pos();
-            move_towards(monster_id, player_x, player_y, &game.map, objects);
-        } else if objects[PLAYER].fighter.map_or(false, |f| f.hp > 0) {
-            // close enough, attack! (if the player is still alive)
-            let (monster, player) = mut_two(monster_id, PLAYER, objects);
-            monster.attack(player);
+    fn set_background(&mut self, x: i32, y: i32, color: palette::Color) {
+        if self.in_bounds(x, y) {
+            self.root
+                .set_char_background(x, y, tcod_color(color), BackgroundFlag::Set);
         }
     }
-}
-
-/// callback for player death
-fn player_death(player: &mut Object) {
-    // the game ended!
-    println!("You died!");
-
-    // for added effect, transform the player into a corpse!
-    player.char = '%';
-    player.color = DARK_RED;
-}
 
-// monster death callback
-fn monster_death(monster: &mut Object) {
-    // transform it into a nasty corpse! it dosen't block, can't be attacked, and dosn't move
-    println!("{} is dead!", monster.name);
-    monster.char = '%';
-    monster.color = DARK_RED;
-    monster.blocks = false;
-    monster.fighter = None;
-    monster.ai = None;
-    monster.name = format!("remains of {}", monster.name);
-}
-
-/// render all of the things
-fn render_all(tcod: &mut Tcod, game: &mut Game, objects: &[Object], fov_recompute: bool) {
-    if fov_recompute {
-        // recompute FOV if needed (the player moved or something)
-        let player = &objects[PLAYER];
-        tcod.fov
-            .compute_fov(player.x, player.y, TORCH_RADIUS, FOV_LIGHT_WALLS, FOV_ALGO);
-    }
-
-    // get the list of objects within the FOV to draw
-    let mut to_draw: Vec<_> = objects
-        .iter()
-        .filter(|o| tcod.fov.is_in_fov(o.x, o.y))
-        .collect();
-    // sort so that non-blocking objects come first
-    to_draw.sort_by(|o1, o2| o1.blocks.cmp(&o2.blocks));
-    // Draw all of our objects on the screen
-    for object in &to_draw {
-        object.draw(&mut tcod.con);
-    }
-
-    // Draw all our tiles onto the screen
-    for y in 0..MAP_HEIGHT {
-        for x in 0..MAP_WIDTH {
-            let visible = tcod.fov.is_in_fov(x, y);
-            let wall = game.map[x as usize][y as usize].block_sight;
-            let color = match (visible, wall) {
-                (false, true) => COLOR_DARK_WALL,
-                (false, false) => COLOR_DARK_GROUND,
-                (true, true) => COLOR_LIGHT_WALL,
-                (true, false) => COLOR_LIGHT_GROUND,
-            };
-            let explored = &mut game.map[x as usize][y as usize].explored;
-            if visible {
-                // since it's visible, explore it
-                *explored = true;
-            }
-            if *explored {
-                tcod.con
-                    .set_char_background(x, y, color, BackgroundFlag::Set);
-            }
+    fn background(&self, x: i32, y: i32) -> palette::Color {
+        if self.in_bounds(x, y) {
+            let color = self.root.get_char_background(x, y);
+            palette::Color::new(color.r, color.g, color.b)
+        } else {
+            palette::BLACK
         }
     }
-    // blit the contents of "con" onto the root console and present it
-    blit(
-        &tcod.con,
-        (0, 0),
-        (SCREEN_WIDTH, SCREEN_HEIGHT),
-        &mut tcod.root,
-        (0, 0),
-        1.0,
-        1.0,
-    );
 
-    // show the player's stats
-    tcod.root.set_default_foreground(WHITE);
-    if let Some(fighter) = objects[PLAYER].fighter {
-        tcod.root.print_ex(
-            1,
-            SCREEN_HEIGHT - 2,
-            BackgroundFlag::None,
-            TextAlignment::Left,
-            format!("HP: {}/{} ", fighter.hp, fighter.max_hp),
-        );
+    fn flush(&mut self) {
+        self.root.flush();
     }
 }
 
-/// Create our Map object
-fn make_map(objects: &mut Vec<Object>) -> Map {
-    // fill map with "unblocked" tiles
-    let mut map = vec![vec![Tile::wall(); MAP_HEIGHT as usize]; MAP_WIDTH as usize];
-    // create the rooms
-    let mut rooms = vec![];
-    for _ in 0..MAX_ROOMS {
-        // random width and height
-        let w = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        let h = rand::thread_rng().gen_range(ROOM_MIN_SIZE, ROOM_MAX_SIZE + 1);
-        // random positions without going off the bounds of the map
-        let x = rand::thread_rng().gen_range(0, MAP_WIDTH - w);
-        let y = rand::thread_rng().gen_range(0, MAP_HEIGHT - h);
-
-        let new_room = Rect::new(x, y, w, h);
-
-        // run through and check if any others intersect
-        let failed = rooms
-            .iter()
-            .any(|other_room| new_room.intersects_with(other_room));
-
-        if !failed {
-            // valid room with no intersections
-
-            // "paint" it to the map's tiles
-            create_room(new_room, &mut map);
-
-            // "fill" it with monsters
-            place_objects(new_room, &map, objects);
-
-            // center coordinates fo the new room, will be useful later
-            let (new_x, new_y) = new_room.center();
-
-            if rooms.is_empty() {
-                // this is the first room, where the player starts at
-                objects[PLAYER].set_pos(new_x, new_y);
-            } else {
-                // all rooms after the first:
-                // connect it to the previous with a tunnel
-
-                // center coords of the previous room
-                let (prev_x, prev_y) = rooms[rooms.len() - 1].center();
-
-                // toss a coin
-                if rand::random() {
-                    // first move horizontally, then vert
-                    create_h_tunnel(prev_x, new_x, prev_y, &mut map);
-                    create_v_tunnel(prev_y, new_y, new_x, &mut map);
+impl Input for Tcod {
+    fn check_for_event(&mut self) -> Option<Event> {
+        match input::check_for_event(input::MOUSE | input::KEY_PRESS) {
+            Some((_, input::Event::Mouse(mouse))) => Some(Event::Mouse(Mouse {
+                x: mouse.cx as i32,
+                y: mouse.cy as i32,
+                left: mouse.lbutton_pressed,
+                right: mouse.rbutton_pressed,
+            })),
+            Some((_, input::Event::Key(key))) => {
+                if self.toggle_fullscreen(key) {
+                    None
                 } else {
-                    // first move vert, then horiz
-                    create_v_tunnel(prev_y, new_y, prev_x, &mut map);
-                    create_h_tunnel(prev_x, new_x, new_y, &mut map);
+                    keypress(key).map(Event::Key)
                 }
             }
-
-            // append the new room to the list
-            rooms.push(new_room);
+            None => None,
         }
     }
 
-    map
-}
-
-/// Create a room within our map
-fn create_room(room: Rect, map: &mut Map) {
-    // go through the tiles within the rectangle and make them passable
-    for x in (room.x1 + 1)..room.x2 {
-        for y in (room.y1 + 1)..room.y2 {
-            map[x as usize][y as usize] = Tile::empty();
-        }
-    }
-}
-
-/// Create a horizontal tunnel between two rooms
-fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
-    // horizontal tunnel.  `min()` and `map()` are used in case `x1 > x2`
-    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
-    }
-}
-
-/// Create a vertical tunnel between two rooms
-fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
-    // vertical tunnel
-    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
-        map[x as usize][y as usize] = Tile::empty();
-    }
-}
-
-/// Place some monsters in a room
-fn place_objects(room: Rect, map: &Map, objects: &mut Vec<Object>) {
-    // chose a random number of monsters
-    let num_monsters = rand::thread_rng().gen_range(0, MAX_ROOM_MONSTERS + 1);
-
-    for _ in 0..num_monsters {
-        // chose random spot for this monster
-        let x = rand::thread_rng().gen_range(room.x1 + 1, room.x2);
-        let y = rand::thread_rng().gen_range(room.y1 + 1, room.y2);
-        if !is_blocked(x, y, map, objects) {
-            let mut monster = if rand::random::<f32>() < 0.8 {
-                // 80% chance of getting an orc
-                // create an orc
-                let mut orc = Object::new(x, y, 'o', DESATURATED_GREEN, true, "orc");
-                orc.fighter = Some(Fighter {
-                    max_hp: 10,
-                    hp: 10,
-                    defense: 0,
-                    power: 3,
-                    on_death: DeathCallback::Monster,
-                });
-                orc.ai = Some(Ai::Basic);
-                orc
-            } else {
-                let mut troll = Object::new(x, y, 'T', DARKER_GREEN, true, "troll");
-                troll.fighter = Some(Fighter {
-                    max_hp: 16,
-                    hp: 16,
-                    defense: 1,
-                    power: 4,
-                    on_death: DeathCallback::Monster,
-                });
-                troll.ai = Some(Ai::Basic);
-                troll
-            };
-            monster.alive = true;
-            objects.push(monster);
+    fn wait_for_keypress(&mut self) -> Option<Keypress> {
+        while !self.root.window_closed() {
+            let key = self.root.wait_for_keypress(true);
+            if self.toggle_fullscreen(key) {
+                continue;
+            }
+            if let Some(key) = keypress(key) {
+                return Some(key);
+            }
         }
+        None
     }
-}
 
-/// check if a tile is blocked
-fn is_blocked(x: i32, y: i32, map: &Map, objects: &[Object]) -> bool {
-    // first test the map tile
-    if map[x as usize][y as usize].blocked {
-        return true;
+    fn is_closed(&self) -> bool {
+        self.root.window_closed()
     }
-    // now objects
-    objects
-        .iter()
-        .any(|object| object.blocks && object.pos() == (x, y))
 }
 
-/// Mutably borrow two *separate* elements from the given slice.
-/// Panics when the indexes are equal or out of bounds.
-fn mut_two<T>(first_index: usize, second_index: usize, items: &mut [T]) -> (&mut T, &mut T) {
-    assert!(first_index != second_index);
-    let split_at_index = cmp::max(first_index, second_index);
-    let (first_slice, second_slice) = items.split_at_mut(split_at_index);
-    if first_index < second_index {
-        (&mut first_slice[first_index], &mut second_slice[0])
-    } else {
-        (&mut second_slice[0], &mut first_slice[second_index])
-    }
+/// convert a color from the game core into its tcod equivalent
+fn tcod_color(color: palette::Color) -> Color {
+    Color::new(color.r, color.g, color.b)
 }
 
-/// Handle Key inputs from the user
-fn handle_keys(tcod: &mut Tcod, objects: &mut [Object], game: &Game) -> PlayerAction {
-    use tcod::input::Key;
+/// translate a tcod key into the key the bindings know about
+fn keypress(key: Key) -> Option<Keypress> {
     use tcod::input::KeyCode::*;
-    use PlayerAction::*;
-
-    // TODO: Actually handle
-    let key = tcod.root.wait_for_keypress(true);
-    let player_alive = objects[PLAYER].alive;
-    match (key, key.text(), player_alive) {
-        // Alt + Enter: toggle fullscreen
-        (
-            Key {
-                code: Enter,
-                alt: true,
-                ..
-            },
-            _,
-            _,
-        ) => {
-            let fullscreen = tcod.root.is_fullscreen();
-            tcod.root.set_fullscreen(!fullscreen);
-            DidntTakeTurn
-        }
-        // Exit the game when the escape key is pressed
-        (Key { code: Escape, .. }, _, _) => Exit,
-        // movement keys
-        (Key { code: Up, .. }, _, true) => {
-            player_move_or_attack(0, -1, &game, objects);
-            TookTurn
-        }
-        (Key { code: Down, .. }, _, true) => {
-            player_move_or_attack(0, 1, &game, objects);
-            TookTurn
-        }
-        (Key { code: Left, .. }, _, true) => {
-            player_move_or_attack(-1, 0, &game, objects);
-            TookTurn
-        }
-        (Key { code: Right, .. }, _, true) => {
-            player_move_or_attack(1, 0, &game, objects);
-            TookTurn
-        }
-
-        _ => DidntTakeTurn,
-    }
+    let keypress = match key.code {
+        Text => Keypress::Char(key.text().chars().next()?),
+        Up => Keypress::Up,
+        Down => Keypress::Down,
+        Left => Keypress::Left,
+        Right => Keypress::Right,
+        Home => Keypress::Home,
+        End => Keypress::End,
+        PageUp => Keypress::PageUp,
+        PageDown => Keypress::PageDown,
+        Escape => Keypress::Escape,
+        Enter | NumPadEnter => Keypress::Enter,
+        NumPad0 => Keypress::NumPad(0),
+        NumPad1 => Keypress::NumPad(1),
+        NumPad2 => Keypress::NumPad(2),
+        NumPad3 => Keypress::NumPad(3),
+        NumPad4 => Keypress::NumPad(4),
+        NumPad5 => Keypress::NumPad(5),
+        NumPad6 => Keypress::NumPad(6),
+        NumPad7 => Keypress::NumPad(7),
+        NumPad8 => Keypress::NumPad(8),
+        NumPad9 => Keypress::NumPad(9),
+        _ => return None,
+    };
+    Some(keypress)
 }
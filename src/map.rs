@@ -0,0 +1,440 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::colors::*;
+use crate::game::GameRng;
+use crate::mapgen::{add_water_and_chasms, connect_regions, furthest_from, Level, MapGenerator};
+use crate::object::{Equipment, Item, Object, Slot};
+use crate::spawns::SpawnTable;
+use crate::world::{Bundle, Storage, World};
+
+/*********  CONSTANTS  **********/
+/// how far from the first of a pack the rest of it can spawn
+const PACK_SPREAD: i32 = 2;
+/// size of the map; it can be bigger than the screen, which scrolls to follow the player
+pub const MAP_WIDTH: i32 = 120;
+pub const MAP_HEIGHT: i32 = 80;
+/// Tile colors, in view (light) and remembered (dark)
+const COLOR_DARK_WALL: Color = Color::new(0, 0, 100);
+const COLOR_LIGHT_WALL: Color = Color::new(130, 110, 50);
+const COLOR_DARK_GROUND: Color = Color::new(50, 50, 150);
+const COLOR_LIGHT_GROUND: Color = Color::new(200, 180, 50);
+const COLOR_DARK_WATER: Color = Color::new(20, 30, 110);
+const COLOR_LIGHT_WATER: Color = Color::new(40, 90, 200);
+const COLOR_DARK_DOOR: Color = Color::new(60, 40, 20);
+const COLOR_LIGHT_DOOR: Color = Color::new(120, 70, 20);
+
+/*********  STRUCTURES  *********/
+/// A tile of the map and it's prooerties
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Tile {
+    pub kind: TileKind,
+    pub explored: bool,
+}
+
+/// What a tile is made of, which decides how it looks and what can get through
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileKind {
+    Floor,
+    Wall,
+    OpenDoor,
+    /// opens when something walks into it
+    ClosedDoor,
+    /// looks and acts like a wall until it's found by searching
+    SecretDoor,
+    /// shallow enough to wade through
+    Water,
+    /// can be seen across, but not crossed
+    Chasm,
+}
+
+/// a list of list of tiles, to represent the map
+pub type Map = Vec<Vec<Tile>>;
+
+/// A value that changes as the player goes deeper: it applies from `level` on,
+/// until the next transition in the table
+#[derive(Clone, Copy, Debug)]
+struct Transition {
+    level: u32,
+    value: u32,
+}
+
+/// A rectangle on the map, used to characterise a room.
+#[derive(Clone, Copy, Debug)]
+pub struct Rect {
+    pub x1: i32,
+    pub y1: i32,
+    pub x2: i32,
+    pub y2: i32,
+}
+
+/// Functions for the tile structure
+impl Tile {
+    pub fn new(kind: TileKind) -> Self {
+        Tile {
+            kind,
+            explored: false,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Tile::new(TileKind::Floor)
+    }
+
+    pub fn wall() -> Self {
+        Tile::new(TileKind::Wall)
+    }
+
+    /// whether it can't be walked onto as it is now
+    pub fn blocked(&self) -> bool {
+        self.kind.blocks_movement()
+    }
+
+    /// whether it can't be seen through
+    pub fn block_sight(&self) -> bool {
+        self.kind.blocks_sight()
+    }
+}
+
+impl TileKind {
+    pub fn blocks_movement(self) -> bool {
+        use TileKind::*;
+        match self {
+            Floor | OpenDoor | Water => false,
+            Wall | ClosedDoor | SecretDoor | Chasm => true,
+        }
+    }
+
+    pub fn blocks_sight(self) -> bool {
+        use TileKind::*;
+        match self {
+            Floor | OpenDoor | Water | Chasm => false,
+            Wall | ClosedDoor | SecretDoor => true,
+        }
+    }
+
+    /// Whether it's a way through, even if a door has to be opened or found
+    /// first. This is what counts when making sure a level is connected.
+    pub fn is_passage(self) -> bool {
+        !matches!(self, TileKind::Wall | TileKind::Chasm)
+    }
+
+    /// the character drawn on it
+    pub fn glyph(self) -> char {
+        use TileKind::*;
+        match self {
+            Floor | Wall | SecretDoor => ' ',
+            OpenDoor => '\'',
+            ClosedDoor => '+',
+            Water => '~',
+            Chasm => ':',
+        }
+    }
+
+    /// The (foreground, background) colors to draw it in, either in view or
+    /// as it is remembered
+    pub fn colors(self, visible: bool) -> (Color, Color) {
+        use TileKind::*;
+        let ground = if visible {
+            COLOR_LIGHT_GROUND
+        } else {
+            COLOR_DARK_GROUND
+        };
+        match (self, visible) {
+            (Floor, _) => (WHITE, ground),
+            (Wall, true) | (SecretDoor, true) => (WHITE, COLOR_LIGHT_WALL),
+            (Wall, false) | (SecretDoor, false) => (WHITE, COLOR_DARK_WALL),
+            (OpenDoor, true) | (ClosedDoor, true) => (COLOR_LIGHT_DOOR, ground),
+            (OpenDoor, false) | (ClosedDoor, false) => (COLOR_DARK_DOOR, ground),
+            (Water, true) => (LIGHT_CYAN, COLOR_LIGHT_WATER),
+            (Water, false) => (DARK_BLUE, COLOR_DARK_WATER),
+            (Chasm, true) => (GREY, BLACK),
+            (Chasm, false) => (DARKER_GREY, BLACK),
+        }
+    }
+}
+
+/// Functinos for the rectangle structure
+impl Rect {
+    pub fn new(x: i32, y: i32, w: i32, h: i32) -> Self {
+        Rect {
+            x1: x,
+            x2: x + w,
+            y1: y,
+            y2: y + h,
+        }
+    }
+
+    pub fn center(&self) -> (i32, i32) {
+        let center_x = (self.x1 + self.x2) / 2;
+        let center_y = (self.y1 + self.y2) / 2;
+        (center_x, center_y)
+    }
+
+    pub fn intersects_with(&self, other: &Rect) -> bool {
+        // returns tru if this rectangle intersects with another one
+        (self.x1 <= other.x2)
+            && (self.x2 > other.x1)
+            && (self.y1 <= other.y2)
+            && (self.y2 >= other.y1)
+    }
+}
+
+/// Create our Map object for the given dungeon level, dug by `generator`.
+/// Every random choice is drawn from `rng`, so the same seed always builds the
+/// same dungeon. Monsters come from `spawns`.
+pub fn make_map(
+    world: &mut World,
+    rng: &mut GameRng,
+    level: u32,
+    spawns: &SpawnTable,
+    generator: &dyn MapGenerator,
+) -> Map {
+    let Level {
+        mut map,
+        rooms,
+        start,
+    } = generator.generate(MAP_WIDTH, MAP_HEIGHT, rng);
+    add_water_and_chasms(&mut map, start, rng);
+    // whatever the generator did, every open tile can be walked to
+    connect_regions(&mut map, start, rng);
+
+    // put the player in first, so nothing is placed on top of them
+    world.objects[world.player].set_pos(start.0, start.1);
+    for room in rooms {
+        // "fill" it with monsters and items
+        place_objects(room, &map, world, rng, level, spawns);
+    }
+
+    // create stairs as far from the player as they can be
+    let (stairs_x, stairs_y) = furthest_from(&map, start);
+    let mut stairs = Object::new(stairs_x, stairs_y, '>', WHITE, false, "stairs");
+    stairs.always_visible = true;
+    world.spawn(Bundle::new(stairs));
+
+    map
+}
+
+/// Returns a value that depends on level. the table specifies what
+/// value occurs after each level, default is 0.
+fn from_dungeon_level(table: &[Transition], level: u32) -> u32 {
+    table
+        .iter()
+        .rev()
+        .find(|transition| level >= transition.level)
+        .map_or(0, |transition| transition.value)
+}
+
+/// Pick one of `choices` at random, each as likely as its weight
+fn random_choice<T: Copy>(choices: &[(T, u32)], rng: &mut GameRng) -> T {
+    let weights = WeightedIndex::new(choices.iter().map(|&(_, weight)| weight))
+        .expect("a spawn table needs at least one entry with a weight");
+    choices[weights.sample(rng)].0
+}
+
+/// Place some monsters and items in a room, more and nastier the deeper it is
+fn place_objects(
+    room: Rect,
+    map: &Map,
+    world: &mut World,
+    rng: &mut GameRng,
+    level: u32,
+    spawns: &SpawnTable,
+) {
+    // chose a random number of monsters, up to what this depth allows
+    let num_monsters = rng.gen_range(0..=spawns.max_per_room(level));
+
+    for _ in 0..num_monsters {
+        // chose random spot for this monster
+        let x = rng.gen_range(room.x1 + 1..room.x2);
+        let y = rng.gen_range(room.y1 + 1..room.y2);
+        if !is_blocked(x, y, map, &world.objects) {
+            // pick one from the spawn table; there may be nothing this deep
+            if let Some(template) = spawns.choose_monster(level, rng) {
+                let first = world.spawn(template.spawn(x, y));
+                if let Some(pack) = template.pack {
+                    world.insert(first, |w| &mut w.packs, first);
+                    let size = rng.gen_range(pack.min..=pack.max);
+                    for _ in 1..size {
+                        // the rest crowd round the first, wherever there's room
+                        let free: Vec<_> = (x - PACK_SPREAD..=x + PACK_SPREAD)
+                            .flat_map(|x| (y - PACK_SPREAD..=y + PACK_SPREAD).map(move |y| (x, y)))
+                            .filter(|&(x, y)| {
+                                x > room.x1
+                                    && x < room.x2
+                                    && y > room.y1
+                                    && y < room.y2
+                                    && !is_blocked(x, y, map, &world.objects)
+                            })
+                            .collect();
+                        if free.is_empty() {
+                            break;
+                        }
+                        let (x, y) = free[rng.gen_range(0..free.len())];
+                        let member = world.spawn(template.spawn(x, y));
+                        world.insert(member, |w| &mut w.packs, first);
+                    }
+                }
+            }
+        }
+    }
+
+    // maximum number of items per room
+    let max_items = from_dungeon_level(
+        &[
+            Transition { level: 1, value: 1 },
+            Transition { level: 4, value: 2 },
+        ],
+        level,
+    );
+
+    // item random table
+    let item_chances = &[
+        // healing potion always shows up, even if all other items have 0 chance
+        (Item::Heal, 35),
+        (
+            Item::Regeneration,
+            from_dungeon_level(
+                &[Transition {
+                    level: 2,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Haste,
+            from_dungeon_level(
+                &[Transition {
+                    level: 3,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Strength,
+            from_dungeon_level(
+                &[Transition {
+                    level: 3,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Lightning,
+            from_dungeon_level(
+                &[Transition {
+                    level: 4,
+                    value: 25,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Fireball,
+            from_dungeon_level(
+                &[Transition {
+                    level: 6,
+                    value: 25,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Confuse,
+            from_dungeon_level(
+                &[Transition {
+                    level: 2,
+                    value: 10,
+                }],
+                level,
+            ),
+        ),
+        (
+            Item::Sword,
+            from_dungeon_level(&[Transition { level: 4, value: 5 }], level),
+        ),
+        (
+            Item::Shield,
+            from_dungeon_level(
+                &[Transition {
+                    level: 8,
+                    value: 15,
+                }],
+                level,
+            ),
+        ),
+    ];
+
+    // choose random number of items
+    let num_items = rng.gen_range(0..=max_items);
+
+    for _ in 0..num_items {
+        // choose random spot for this item
+        let x = rng.gen_range(room.x1 + 1..room.x2);
+        let y = rng.gen_range(room.y1 + 1..room.y2);
+
+        // only place it if the tile is not blocked
+        if !is_blocked(x, y, map, &world.objects) {
+            let kind = random_choice(item_chances, rng);
+            let object = match kind {
+                // create a healing potion
+                Item::Heal => Object::new(x, y, '!', VIOLET, false, "healing potion"),
+                // and the potions that last a while
+                Item::Regeneration => {
+                    Object::new(x, y, '!', LIGHT_VIOLET, false, "potion of regeneration")
+                }
+                Item::Haste => Object::new(x, y, '!', SKY, false, "potion of haste"),
+                Item::Strength => Object::new(x, y, '!', ORANGE, false, "potion of strength"),
+                // create a lightning bolt scroll
+                Item::Lightning => {
+                    Object::new(x, y, '#', LIGHT_YELLOW, false, "scroll of lightning bolt")
+                }
+                // create a fireball scroll
+                Item::Fireball => Object::new(x, y, '#', LIGHT_YELLOW, false, "scroll of fireball"),
+                // create a confuse scroll
+                Item::Confuse => Object::new(x, y, '#', LIGHT_YELLOW, false, "scroll of confusion"),
+                // create a sword
+                Item::Sword => Object::new(x, y, '/', SKY, false, "sword"),
+                // create a shield
+                Item::Shield => Object::new(x, y, '[', DARKER_ORANGE, false, "shield"),
+            };
+            let mut item = Bundle::new(object);
+            item.item = Some(kind);
+            // what the equipment gives whoever wears it
+            item.equipment = match kind {
+                Item::Sword => Some(Equipment {
+                    slot: Slot::RightHand,
+                    equipped: false,
+                    power_bonus: 3,
+                    defense_bonus: 0,
+                    max_hp_bonus: 0,
+                }),
+                Item::Shield => Some(Equipment {
+                    slot: Slot::LeftHand,
+                    equipped: false,
+                    power_bonus: 0,
+                    defense_bonus: 1,
+                    max_hp_bonus: 0,
+                }),
+                _ => None,
+            };
+            world.spawn(item);
+        }
+    }
+}
+
+/// check if a tile is blocked
+pub fn is_blocked(x: i32, y: i32, map: &Map, objects: &Storage<Object>) -> bool {
+    // first test the map tile
+    if map[x as usize][y as usize].blocked() {
+        return true;
+    }
+    // now objects
+    objects
+        .values()
+        .any(|object| object.blocks && object.pos() == (x, y))
+}
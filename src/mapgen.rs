@@ -0,0 +1,539 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cmp;
+use std::collections::VecDeque;
+
+use crate::game::GameRng;
+use crate::map::{Map, Rect, Tile, TileKind};
+use crate::path::DIRECTIONS;
+
+/*********  CONSTANTS  **********/
+/// Room settings
+const ROOM_MAX_SIZE: i32 = 10;
+const ROOM_MIN_SIZE: i32 = 6;
+const MAX_ROOMS: i32 = 60;
+/// BSP: the smallest area that is still split in two
+const BSP_MIN_LEAF_SIZE: i32 = 12;
+/// caves: how much starts out as wall, in percent, and how often it's smoothed
+const CAVE_WALL_PERCENT: u32 = 45;
+const CAVE_SMOOTHING_STEPS: i32 = 5;
+/// drunkard's walk: how much of the map to dig out, in percent, and how far
+/// each drunkard staggers before the next one starts
+const DRUNKARD_FLOOR_PERCENT: usize = 40;
+const DRUNKARD_STEPS: i32 = 400;
+/// Maps without rooms are cut into squares this big to scatter objects over
+const SPAWN_AREA_SIZE: i32 = 16;
+/// where a tunnel meets a room, the chances out of 100 of a closed door, an
+/// open one and a secret one; otherwise it's left as a doorway
+const CLOSED_DOOR_CHANCE: u32 = 40;
+const OPEN_DOOR_CHANCE: u32 = 20;
+const SECRET_DOOR_CHANCE: u32 = 10;
+/// the most pools of water and chasms on a level, and how big they can be
+const MAX_POOLS: u32 = 4;
+const MAX_CHASMS: u32 = 2;
+const MAX_FEATURE_RADIUS: i32 = 3;
+
+/*********  STRUCTURES  *********/
+/// A freshly dug level, before anything is put in it
+pub struct Level {
+    pub map: Map,
+    /// the areas monsters and items are placed in, usually the rooms
+    pub rooms: Vec<Rect>,
+    /// where the player arrives
+    pub start: (i32, i32),
+}
+
+/// A way of digging out a level
+pub trait MapGenerator {
+    /// Dig a level of `width` by `height` tiles, drawing every random choice
+    /// from `rng`. The outer edge must be left as wall. Parts of the level
+    /// don't have to be connected; `make_map` joins them up afterwards.
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> Level;
+}
+
+/// Random non-overlapping rooms, each joined to the previous one by an
+/// L-shaped tunnel
+pub struct RoomsAndCorridors;
+
+/// The map is split in two again and again, a room is put in each of the
+/// smallest parts, and rooms are joined up the way they were split
+pub struct Bsp;
+
+/// Random noise smoothed into natural looking caves
+pub struct Caves;
+
+/// Tunnels dug by staggering about at random
+pub struct DrunkardsWalk;
+
+/// The generators by name, as they are given in the spawn table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MapStyle {
+    RoomsAndCorridors,
+    Bsp,
+    Caves,
+    DrunkardsWalk,
+}
+
+impl MapGenerator for RoomsAndCorridors {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> Level {
+        let mut map = new_walls(width, height);
+        let mut rooms: Vec<Rect> = vec![];
+        for _ in 0..MAX_ROOMS {
+            // random width and height
+            let w = rng.gen_range(ROOM_MIN_SIZE..=ROOM_MAX_SIZE);
+            let h = rng.gen_range(ROOM_MIN_SIZE..=ROOM_MAX_SIZE);
+            // random positions without going off the bounds of the map
+            let x = rng.gen_range(0..width - w);
+            let y = rng.gen_range(0..height - h);
+
+            let new_room = Rect::new(x, y, w, h);
+
+            // run through and check if any others intersect
+            let failed = rooms
+                .iter()
+                .any(|other_room| new_room.intersects_with(other_room));
+
+            if !failed {
+                // "paint" it to the map's tiles
+                create_room(new_room, &mut map);
+
+                // all rooms after the first: connect it to the previous with a tunnel
+                if let Some(previous) = rooms.last() {
+                    connect_rooms(previous.center(), new_room.center(), &mut map, rng);
+                }
+                rooms.push(new_room);
+            }
+        }
+        add_doors(&rooms, &mut map, rng);
+
+        // the first room is where the player starts
+        let start = match rooms.first() {
+            Some(room) => room.center(),
+            None => dig_start(&mut map),
+        };
+        Level { map, rooms, start }
+    }
+}
+
+impl MapGenerator for Bsp {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> Level {
+        let mut map = new_walls(width, height);
+        let mut rooms = vec![];
+        bsp_split(Rect::new(0, 0, width, height), &mut map, &mut rooms, rng);
+        add_doors(&rooms, &mut map, rng);
+        let start = rooms[0].center();
+        Level { map, rooms, start }
+    }
+}
+
+/// Fill `area` with rooms, splitting it if it's big enough. Returns the room
+/// that the area's rooms are joined to the rest of the map through.
+fn bsp_split(area: Rect, map: &mut Map, rooms: &mut Vec<Rect>, rng: &mut GameRng) -> Rect {
+    let width = area.x2 - area.x1;
+    let height = area.y2 - area.y1;
+    let can_split_x = width >= 2 * BSP_MIN_LEAF_SIZE;
+    let can_split_y = height >= 2 * BSP_MIN_LEAF_SIZE;
+    if !can_split_x && !can_split_y {
+        // a leaf: put a room somewhere inside it, keeping off its far edges so
+        // it can't touch the next leaf's room
+        let w = rng.gen_range(ROOM_MIN_SIZE..width);
+        let h = rng.gen_range(ROOM_MIN_SIZE..height);
+        let x = rng.gen_range(area.x1..area.x2 - w);
+        let y = rng.gen_range(area.y1..area.y2 - h);
+        let room = Rect::new(x, y, w, h);
+        create_room(room, map);
+        rooms.push(room);
+        return room;
+    }
+
+    // split across the longer side
+    let (first, second) = if can_split_x && (!can_split_y || width >= height) {
+        let split = rng.gen_range(area.x1 + BSP_MIN_LEAF_SIZE..=area.x2 - BSP_MIN_LEAF_SIZE);
+        (
+            Rect::new(area.x1, area.y1, split - area.x1, height),
+            Rect::new(split, area.y1, area.x2 - split, height),
+        )
+    } else {
+        let split = rng.gen_range(area.y1 + BSP_MIN_LEAF_SIZE..=area.y2 - BSP_MIN_LEAF_SIZE);
+        (
+            Rect::new(area.x1, area.y1, width, split - area.y1),
+            Rect::new(area.x1, split, width, area.y2 - split),
+        )
+    };
+    let first_room = bsp_split(first, map, rooms, rng);
+    let second_room = bsp_split(second, map, rooms, rng);
+    connect_rooms(first_room.center(), second_room.center(), map, rng);
+    first_room
+}
+
+impl MapGenerator for Caves {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> Level {
+        // start from noise
+        let mut map = new_walls(width, height);
+        for x in 1..width - 1 {
+            for y in 1..height - 1 {
+                if rng.gen_range(0..100) >= CAVE_WALL_PERCENT {
+                    map[x as usize][y as usize] = Tile::empty();
+                }
+            }
+        }
+
+        // smooth it out: a tile mostly surrounded by wall becomes wall, and
+        // one mostly surrounded by floor becomes floor
+        for _ in 0..CAVE_SMOOTHING_STEPS {
+            let previous = map.clone();
+            for x in 1..width - 1 {
+                for y in 1..height - 1 {
+                    let walls = (x - 1..=x + 1)
+                        .flat_map(|nx| (y - 1..=y + 1).map(move |ny| (nx, ny)))
+                        .filter(|&(nx, ny)| previous[nx as usize][ny as usize].blocked())
+                        .count();
+                    map[x as usize][y as usize] = if walls >= 5 {
+                        Tile::wall()
+                    } else {
+                        Tile::empty()
+                    };
+                }
+            }
+        }
+
+        // start somewhere open
+        let floors = floor_tiles(&map);
+        let start = match floors.choose(rng) {
+            Some(&pos) => pos,
+            None => dig_start(&mut map),
+        };
+        let rooms = spawn_areas(width, height);
+        Level { map, rooms, start }
+    }
+}
+
+impl MapGenerator for DrunkardsWalk {
+    fn generate(&self, width: i32, height: i32, rng: &mut GameRng) -> Level {
+        let mut map = new_walls(width, height);
+        let start = dig_start(&mut map);
+        let wanted = (width * height) as usize * DRUNKARD_FLOOR_PERCENT / 100;
+
+        let mut dug = 1;
+        while dug < wanted {
+            // each drunkard starts somewhere already dug, so it's all connected
+            let floors = floor_tiles(&map);
+            let (mut x, mut y) = *floors.choose(rng).unwrap_or(&start);
+            for _ in 0..DRUNKARD_STEPS {
+                let (dx, dy) = *[(0, -1), (0, 1), (-1, 0), (1, 0)]
+                    .choose(rng)
+                    .unwrap_or(&(0, 0));
+                // stay off the outer edge
+                x = (x + dx).clamp(1, width - 2);
+                y = (y + dy).clamp(1, height - 2);
+                let tile = &mut map[x as usize][y as usize];
+                if tile.kind == TileKind::Wall {
+                    *tile = Tile::empty();
+                    dug += 1;
+                }
+            }
+        }
+        let rooms = spawn_areas(width, height);
+        Level { map, rooms, start }
+    }
+}
+
+impl MapStyle {
+    /// the generator that digs levels in this style
+    pub fn generator(self) -> Box<dyn MapGenerator> {
+        match self {
+            MapStyle::RoomsAndCorridors => Box::new(RoomsAndCorridors),
+            MapStyle::Bsp => Box::new(Bsp),
+            MapStyle::Caves => Box::new(Caves),
+            MapStyle::DrunkardsWalk => Box::new(DrunkardsWalk),
+        }
+    }
+}
+
+/// How many steps it takes to walk from `start` to every tile, or `None` for
+/// the tiles that can't be reached. Steps can be diagonal, like the player's.
+pub fn flood_fill(map: &Map, start: (i32, i32)) -> Vec<Vec<Option<u32>>> {
+    let width = map.len() as i32;
+    let height = map.first().map_or(0, |column| column.len()) as i32;
+    let mut distances = vec![vec![None; height as usize]; width as usize];
+    let mut queue = VecDeque::new();
+    distances[start.0 as usize][start.1 as usize] = Some(0);
+    queue.push_back(start);
+    while let Some((x, y)) = queue.pop_front() {
+        let distance = distances[x as usize][y as usize].unwrap_or(0);
+        for &(dx, dy) in &DIRECTIONS {
+            let (nx, ny) = (x + dx, y + dy);
+            if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                continue;
+            }
+            let (ux, uy) = (nx as usize, ny as usize);
+            if map[ux][uy].kind.is_passage() && distances[ux][uy].is_none() {
+                distances[ux][uy] = Some(distance + 1);
+                queue.push_back((nx, ny));
+            }
+        }
+    }
+    distances
+}
+
+/// Make sure every open tile can be reached from `start`, by digging a tunnel
+/// from each part that can't to the nearest tile that can
+pub fn connect_regions(map: &mut Map, start: (i32, i32), rng: &mut GameRng) {
+    loop {
+        let reached = flood_fill(map, start);
+        let unreached = passage_tiles(map)
+            .into_iter()
+            .find(|&(x, y)| reached[x as usize][y as usize].is_none());
+        let (x, y) = match unreached {
+            Some(pos) => pos,
+            None => return,
+        };
+        let nearest = passage_tiles(map)
+            .into_iter()
+            .filter(|&(nx, ny)| reached[nx as usize][ny as usize].is_some())
+            .min_by_key(|&(nx, ny)| (nx - x).pow(2) + (ny - y).pow(2))
+            .unwrap_or(start);
+        connect_rooms(nearest, (x, y), map, rng);
+    }
+}
+
+/// the floor tile that takes the longest to walk to from `start`
+pub fn furthest_from(map: &Map, start: (i32, i32)) -> (i32, i32) {
+    let distances = flood_fill(map, start);
+    floor_tiles(map)
+        .into_iter()
+        .max_by_key(|&(x, y)| distances[x as usize][y as usize])
+        .unwrap_or(start)
+}
+
+/// Put doors where tunnels come into rooms. A doorway is a gap in the room's
+/// wall with wall on both sides of it.
+fn add_doors(rooms: &[Rect], map: &mut Map, rng: &mut GameRng) {
+    for room in rooms {
+        // the ring of wall around the room
+        let top_and_bottom = (room.x1..=room.x2).flat_map(|x| vec![(x, room.y1), (x, room.y2)]);
+        let sides = (room.y1..=room.y2).flat_map(|y| vec![(room.x1, y), (room.x2, y)]);
+        for (x, y) in top_and_bottom.chain(sides) {
+            let is_wall = |x: i32, y: i32| map[x as usize][y as usize].kind == TileKind::Wall;
+            if map[x as usize][y as usize].kind != TileKind::Floor {
+                continue;
+            }
+            let doorway = (is_wall(x - 1, y) && is_wall(x + 1, y))
+                || (is_wall(x, y - 1) && is_wall(x, y + 1));
+            if !doorway {
+                continue;
+            }
+            let roll = rng.gen_range(0..100);
+            let kind = if roll < SECRET_DOOR_CHANCE {
+                TileKind::SecretDoor
+            } else if roll < SECRET_DOOR_CHANCE + CLOSED_DOOR_CHANCE {
+                TileKind::ClosedDoor
+            } else if roll < SECRET_DOOR_CHANCE + CLOSED_DOOR_CHANCE + OPEN_DOOR_CHANCE {
+                TileKind::OpenDoor
+            } else {
+                continue;
+            };
+            map[x as usize][y as usize] = Tile::new(kind);
+        }
+    }
+}
+
+/// Flood a few patches of floor with water and break a few open into chasms,
+/// keeping clear of `start`. A chasm may cut a level in two; `connect_regions`
+/// bridges it again afterwards.
+pub fn add_water_and_chasms(map: &mut Map, start: (i32, i32), rng: &mut GameRng) {
+    let num_pools = rng.gen_range(0..=MAX_POOLS);
+    let num_chasms = rng.gen_range(0..=MAX_CHASMS);
+    let features = (0..num_pools)
+        .map(|_| TileKind::Water)
+        .chain((0..num_chasms).map(|_| TileKind::Chasm))
+        .collect::<Vec<_>>();
+    for kind in features {
+        let floors = floor_tiles(map);
+        let (center_x, center_y) = match floors.choose(rng) {
+            Some(&pos) => pos,
+            None => return,
+        };
+        let radius = rng.gen_range(1..=MAX_FEATURE_RADIUS);
+        for x in center_x - radius..=center_x + radius {
+            for y in center_y - radius..=center_y + radius {
+                let in_radius = (x - center_x).pow(2) + (y - center_y).pow(2) <= radius.pow(2);
+                let near_start = (x - start.0).abs() <= 1 && (y - start.1).abs() <= 1;
+                let in_map = x >= 0 && y >= 0 && x < map.len() as i32 && y < map[0].len() as i32;
+                if in_radius && !near_start && in_map {
+                    let tile = &mut map[x as usize][y as usize];
+                    // only ever on open floor, so doors and walls stay put
+                    if tile.kind == TileKind::Floor {
+                        *tile = Tile::new(kind);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// a map of nothing but wall
+fn new_walls(width: i32, height: i32) -> Map {
+    vec![vec![Tile::wall(); height as usize]; width as usize]
+}
+
+/// every plain floor tile, column by column
+fn floor_tiles(map: &Map) -> Vec<(i32, i32)> {
+    tiles_where(map, |tile| tile.kind == TileKind::Floor)
+}
+
+/// every tile that is some way through, column by column
+fn passage_tiles(map: &Map) -> Vec<(i32, i32)> {
+    tiles_where(map, |tile| tile.kind.is_passage())
+}
+
+fn tiles_where(map: &Map, wanted: impl Fn(&Tile) -> bool) -> Vec<(i32, i32)> {
+    let mut tiles = vec![];
+    for (x, column) in map.iter().enumerate() {
+        for (y, tile) in column.iter().enumerate() {
+            if wanted(tile) {
+                tiles.push((x as i32, y as i32));
+            }
+        }
+    }
+    tiles
+}
+
+/// open up the middle of the map, for when there is nowhere else to start
+fn dig_start(map: &mut Map) -> (i32, i32) {
+    let (x, y) = (map.len() / 2, map[0].len() / 2);
+    map[x][y] = Tile::empty();
+    (x as i32, y as i32)
+}
+
+/// cut the whole map into squares to scatter objects over
+fn spawn_areas(width: i32, height: i32) -> Vec<Rect> {
+    let mut areas = vec![];
+    for x in (0..width - 1).step_by(SPAWN_AREA_SIZE as usize) {
+        for y in (0..height - 1).step_by(SPAWN_AREA_SIZE as usize) {
+            let w = cmp::min(SPAWN_AREA_SIZE, width - 1 - x);
+            let h = cmp::min(SPAWN_AREA_SIZE, height - 1 - y);
+            if w > 1 && h > 1 {
+                areas.push(Rect::new(x, y, w, h));
+            }
+        }
+    }
+    areas
+}
+
+/// join two points with an L-shaped tunnel, going either way round
+fn connect_rooms(
+    (prev_x, prev_y): (i32, i32),
+    (new_x, new_y): (i32, i32),
+    map: &mut Map,
+    rng: &mut GameRng,
+) {
+    // toss a coin
+    if rng.gen() {
+        // first move horizontally, then vert
+        create_h_tunnel(prev_x, new_x, prev_y, map);
+        create_v_tunnel(prev_y, new_y, new_x, map);
+    } else {
+        // first move vert, then horiz
+        create_v_tunnel(prev_y, new_y, prev_x, map);
+        create_h_tunnel(prev_x, new_x, new_y, map);
+    }
+}
+
+/// Create a room within our map
+fn create_room(room: Rect, map: &mut Map) {
+    // go through the tiles within the rectangle and make them passable
+    for x in (room.x1 + 1)..room.x2 {
+        for y in (room.y1 + 1)..room.y2 {
+            map[x as usize][y as usize] = Tile::empty();
+        }
+    }
+}
+
+/// Create a horizontal tunnel between two rooms
+fn create_h_tunnel(x1: i32, x2: i32, y: i32, map: &mut Map) {
+    // horizontal tunnel.  `min()` and `map()` are used in case `x1 > x2`
+    for x in cmp::min(x1, x2)..(cmp::max(x1, x2) + 1) {
+        map[x as usize][y as usize] = Tile::empty();
+    }
+}
+
+/// Create a vertical tunnel between two rooms
+fn create_v_tunnel(y1: i32, y2: i32, x: i32, map: &mut Map) {
+    // vertical tunnel
+    for y in cmp::min(y1, y2)..(cmp::max(y1, y2) + 1) {
+        map[x as usize][y as usize] = Tile::empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::WHITE;
+    use crate::map::{make_map, MAP_HEIGHT, MAP_WIDTH};
+    use crate::object::Object;
+    use crate::spawns::SpawnTable;
+    use crate::world::{Bundle, World};
+    use rand::SeedableRng;
+
+    #[test]
+    fn every_level_is_connected_and_walled_in() {
+        let generators: [(&str, &dyn MapGenerator); 4] = [
+            ("rooms and corridors", &RoomsAndCorridors),
+            ("bsp", &Bsp),
+            ("caves", &Caves),
+            ("drunkard's walk", &DrunkardsWalk),
+        ];
+        let spawns = SpawnTable::default();
+        for (name, generator) in generators {
+            for seed in 0..20 {
+                let mut rng = GameRng::seed_from_u64(seed);
+                let player = Object::new(0, 0, '@', WHITE, true, "player");
+                let mut world = World::new(Bundle::new(player));
+                let map = make_map(&mut world, &mut rng, 1, &spawns, generator);
+
+                let start = world.objects[world.player].pos();
+                let reached = flood_fill(&map, start);
+                for x in 0..MAP_WIDTH {
+                    for y in 0..MAP_HEIGHT {
+                        let kind = map[x as usize][y as usize].kind;
+                        let border = x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+                        if border {
+                            assert_eq!(
+                                kind,
+                                TileKind::Wall,
+                                "{} seed {} at ({}, {})",
+                                name,
+                                seed,
+                                x,
+                                y
+                            );
+                        } else if kind.is_passage() {
+                            assert!(
+                                reached[x as usize][y as usize].is_some(),
+                                "{} seed {}: ({}, {}) can't be reached",
+                                name,
+                                seed,
+                                x,
+                                y
+                            );
+                        }
+                    }
+                }
+                let (stairs_x, stairs_y) = world
+                    .objects
+                    .values()
+                    .find(|object| object.name == "stairs")
+                    .unwrap()
+                    .pos();
+                assert!(
+                    reached[stairs_x as usize][stairs_y as usize].is_some(),
+                    "{} seed {}: the stairs can't be reached",
+                    name,
+                    seed
+                );
+            }
+        }
+    }
+}
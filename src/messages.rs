@@ -0,0 +1,78 @@
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::colors::Color;
+
+/*********  CONSTANTS  **********/
+/// how many messages the log keeps; older ones are dropped, so the log (and
+/// every save file) doesn't keep growing
+pub const MAX_MESSAGES: usize = 500;
+
+/*********  STRUCTURES  *********/
+/// A single line in the message log
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// the turn the message was logged on
+    pub turn: u32,
+    pub text: String,
+    pub color: Color,
+}
+
+/// The message log: the last `MAX_MESSAGES` things that happened, oldest first
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Messages {
+    messages: VecDeque<Message>,
+}
+
+impl Messages {
+    pub fn new() -> Self {
+        Messages {
+            messages: VecDeque::new(),
+        }
+    }
+
+    /// add a new message to the end of the log, dropping the oldest if it's full
+    pub fn add<T: Into<String>>(&mut self, turn: u32, text: T, color: Color) {
+        if self.messages.len() >= MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+        self.messages.push_back(Message {
+            turn,
+            text: text.into(),
+            color,
+        });
+    }
+
+    /// iterate over the messages, oldest first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Message> {
+        self.messages.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::WHITE;
+
+    #[test]
+    fn the_oldest_messages_are_dropped_once_the_log_is_full() {
+        let mut messages = Messages::new();
+        for turn in 0..MAX_MESSAGES as u32 + 10 {
+            messages.add(turn, format!("message {}", turn), WHITE);
+        }
+        assert_eq!(messages.len(), MAX_MESSAGES);
+        assert_eq!(messages.iter().next().unwrap().text, "message 10");
+        assert_eq!(
+            messages.iter().last().unwrap().text,
+            format!("message {}", MAX_MESSAGES + 9)
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::colors::*;
+use crate::effects::{self, EffectKind, StatusEffect};
+use crate::game::{Game, ATTACK_COST, NORMAL_SPEED};
+use crate::world::{Entity, World};
+
+/*********  STRUCTURES  *********/
+/// This is a generic object: the player, a monster, an item, the stairs...
+/// It's always represented by a charactor on the screen. What else it is
+/// depends on its components in the `World`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Object {
+    pub x: i32,
+    pub y: i32,
+    pub char: char,
+    pub color: Color,
+    pub name: String,
+    pub blocks: bool,
+    pub alive: bool,
+    /// still drawn when out of sight, once its tile has been explored
+    pub always_visible: bool,
+    /// the character level, which goes up with experience
+    pub level: i32,
+}
+
+/// combat-related properties and methods (monster, player, NPC).
+/// The stats here are before any bonuses from equipment or status effects;
+/// see `World::power` etc.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fighter {
+    pub base_max_hp: i32,
+    pub hp: i32,
+    pub base_defense: i32,
+    pub base_power: i32,
+    /// for monsters, what killing them is worth; for the player, what they've earned
+    pub xp: i32,
+    pub on_death: DeathCallback,
+    /// what it's under at the moment, ticked once a turn
+    #[serde(default)]
+    pub effects: Vec<StatusEffect>,
+    /// what its hits do to whoever they hurt, besides the damage
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+    /// the energy it gains each tick, before haste or slow
+    pub speed: i32,
+    /// what it has saved up for its next action; see `game::process_turns`
+    pub energy: i32,
+}
+
+/// Options for AI
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Ai {
+    /// walks up to the player and hits them
+    Basic,
+    /// shoots at the player from up to `range` tiles away, backing off if they
+    /// come too close
+    Ranged { range: i32 },
+    /// badly hurt and running away, until it recovers and `previous_ai` comes back
+    Fleeing { previous_ai: Box<Ai> },
+    /// stumbling around at random, until it wears off and `previous_ai` comes back
+    Confused { previous_ai: Box<Ai>, turns: i32 },
+}
+
+/// Things that can be picked up and used
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Item {
+    Heal,
+    Haste,
+    Strength,
+    Regeneration,
+    Lightning,
+    Fireball,
+    Confuse,
+    Sword,
+    Shield,
+}
+
+/// An object that can be equipped, yielding bonuses.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slot: Slot,
+    pub equipped: bool,
+    pub power_bonus: i32,
+    pub defense_bonus: i32,
+    pub max_hp_bonus: i32,
+}
+
+/// Where a piece of equipment is worn; only one item fits in each
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Slot {
+    LeftHand,
+    RightHand,
+    Head,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeathCallback {
+    Player,
+    Monster,
+}
+
+/// Fucntions for the object structure
+impl Object {
+    pub fn new(x: i32, y: i32, char: char, color: Color, blocks: bool, name: &str) -> Self {
+        Object {
+            x,
+            y,
+            char,
+            color,
+            name: name.into(),
+            blocks,
+            alive: false,
+            always_visible: false,
+            level: 1,
+        }
+    }
+
+    /// return the position of the object
+    pub fn pos(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    /// set the position of the object
+    pub fn set_pos(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    /// return the distance to another object
+    pub fn distance_to(&self, other: &Object) -> f32 {
+        let dx = other.x - self.x;
+        let dy = other.y - self.y;
+        ((dx.pow(2) + dy.pow(2)) as f32).sqrt()
+    }
+
+    /// return the distance to some coordinates
+    pub fn distance(&self, (x, y): (i32, i32)) -> f32 {
+        (((x - self.x).pow(2) + (y - self.y).pow(2)) as f32).sqrt()
+    }
+}
+
+/// Functions for whatever has a `Fighter`
+impl World {
+    /// take a specified amount of damage. Returns the experience it's worth if
+    /// this killed it.
+    pub fn take_damage(&mut self, entity: Entity, damage: i32, game: &mut Game) -> Option<i32> {
+        let fighter = self.fighters.get_mut(entity)?;
+        // apply damage if possible
+        if damage > 0 {
+            fighter.hp -= damage;
+        }
+        // check for death, call the death function
+        if fighter.hp <= 0 {
+            let (xp, on_death) = (fighter.xp, fighter.on_death);
+            self.objects[entity].alive = false;
+            on_death.callback(entity, game, self);
+            return Some(xp);
+        }
+        None
+    }
+
+    /// heal by the given amount, without going over the maximum
+    pub fn heal(&mut self, entity: Entity, amount: i32, game: &Game) {
+        let max_hp = self.max_hp(entity, game);
+        if let Some(fighter) = self.fighters.get_mut(entity) {
+            fighter.hp += amount;
+            if fighter.hp > max_hp {
+                fighter.hp = max_hp;
+            }
+        }
+    }
+
+    /// attack power, including bonuses from equipment and strength
+    pub fn power(&self, entity: Entity, game: &Game) -> i32 {
+        let base_power = self.fighters.get(entity).map_or(0, |f| f.base_power);
+        let bonus: i32 = self
+            .get_all_equipped(entity, game)
+            .iter()
+            .map(|e| e.power_bonus)
+            .sum();
+        let strength: i32 = self
+            .effects(entity)
+            .iter()
+            .filter(|effect| effect.kind == EffectKind::Strength)
+            .map(|effect| effect.magnitude)
+            .sum();
+        base_power + bonus + strength
+    }
+
+    /// defense, including bonuses from equipment
+    pub fn defense(&self, entity: Entity, game: &Game) -> i32 {
+        let base_defense = self.fighters.get(entity).map_or(0, |f| f.base_defense);
+        let bonus: i32 = self
+            .get_all_equipped(entity, game)
+            .iter()
+            .map(|e| e.defense_bonus)
+            .sum();
+        base_defense + bonus
+    }
+
+    /// maximum hit points, including bonuses from equipment
+    pub fn max_hp(&self, entity: Entity, game: &Game) -> i32 {
+        let base_max_hp = self.fighters.get(entity).map_or(0, |f| f.base_max_hp);
+        let bonus: i32 = self
+            .get_all_equipped(entity, game)
+            .iter()
+            .map(|e| e.max_hp_bonus)
+            .sum();
+        base_max_hp + bonus
+    }
+
+    /// returns a list of equipped items
+    pub fn get_all_equipped(&self, entity: Entity, game: &Game) -> Vec<Equipment> {
+        // only the player carries an inventory
+        if entity == self.player {
+            game.inventory
+                .iter()
+                .filter_map(|item| item.equipment)
+                .filter(|equipment| equipment.equipped)
+                .collect()
+        } else {
+            vec![] // other objects have no equipment
+        }
+    }
+
+    /// the status effects it's under; nothing without a fighter
+    pub fn effects(&self, entity: Entity) -> &[StatusEffect] {
+        self.fighters.get(entity).map_or(&[], |f| &f.effects)
+    }
+
+    pub fn has_effect(&self, entity: Entity, kind: EffectKind) -> bool {
+        self.effects(entity)
+            .iter()
+            .any(|effect| effect.kind == kind)
+    }
+
+    /// Put a status effect on it, combined with any of the same kind it
+    /// already has. Does nothing without a fighter.
+    pub fn add_effect(&mut self, entity: Entity, effect: StatusEffect) {
+        if let Some(fighter) = self.fighters.get_mut(entity) {
+            effects::add_effect(&mut fighter.effects, effect);
+        }
+    }
+
+    /// the energy it gains each tick, after haste or slow
+    pub fn speed(&self, entity: Entity) -> i32 {
+        let speed = self.fighters.get(entity).map_or(NORMAL_SPEED, |f| f.speed);
+        if self.has_effect(entity, EffectKind::Haste) {
+            speed * 2
+        } else if self.has_effect(entity, EffectKind::Slow) {
+            (speed / 2).max(1)
+        } else {
+            speed
+        }
+    }
+
+    pub fn energy(&self, entity: Entity) -> i32 {
+        self.fighters.get(entity).map_or(0, |f| f.energy)
+    }
+
+    /// use up energy on an action
+    pub fn spend_energy(&mut self, entity: Entity, cost: i32) {
+        if let Some(fighter) = self.fighters.get_mut(entity) {
+            fighter.energy -= cost;
+        }
+    }
+
+    /// add to the experience earned so far
+    pub fn gain_xp(&mut self, entity: Entity, xp: i32) {
+        if let Some(fighter) = self.fighters.get_mut(entity) {
+            fighter.xp += xp;
+        }
+    }
+
+    pub fn attack(&mut self, attacker: Entity, target: Entity, game: &mut Game) {
+        self.spend_energy(attacker, ATTACK_COST);
+        // a simple formula for the attack damage
+        let damage = self.power(attacker, game) - self.defense(target, game);
+        let (attacker_name, target_name) = (
+            self.objects[attacker].name.clone(),
+            self.objects[target].name.clone(),
+        );
+        if damage > 0 {
+            // make the damage take some damage
+            game.messages.add(
+                game.turn,
+                format!(
+                    "{} attacks {} for {} hit points.",
+                    attacker_name, target_name, damage
+                ),
+                WHITE,
+            );
+            if let Some(xp) = self.take_damage(target, damage, game) {
+                // yield experience to the player
+                self.gain_xp(attacker, xp);
+            } else if let Some((attacker_fighter, target_fighter)) =
+                self.fighters.get_two_mut(attacker, target)
+            {
+                // whatever else the hit does to it
+                if let Some(effect) = attacker_fighter.on_hit {
+                    effects::add_effect(&mut target_fighter.effects, effect);
+                    game.messages.add(
+                        game.turn,
+                        format!("{} is {}!", target_name, effect.kind.adjective()),
+                        effect.kind.color(),
+                    );
+                }
+            }
+        } else {
+            game.messages.add(
+                game.turn,
+                format!(
+                    "{} attacks {} but it has no effect!",
+                    attacker_name, target_name
+                ),
+                WHITE,
+            );
+        }
+    }
+}
+
+impl fmt::Display for Slot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Slot::LeftHand => write!(f, "left hand"),
+            Slot::RightHand => write!(f, "right hand"),
+            Slot::Head => write!(f, "head"),
+        }
+    }
+}
+
+impl DeathCallback {
+    fn callback(self, entity: Entity, game: &mut Game, world: &mut World) {
+        use DeathCallback::*;
+        let callback: fn(Entity, &mut Game, &mut World) = match self {
+            Player => player_death,
+            Monster => monster_death,
+        };
+        callback(entity, game, world);
+    }
+}
+
+/// callback for player death
+fn player_death(player: Entity, game: &mut Game, world: &mut World) {
+    // the game ended!
+    game.messages.add(game.turn, "You died!", RED);
+
+    // for added effect, transform the player into a corpse!
+    let player = &mut world.objects[player];
+    player.char = '%';
+    player.color = DARK_RED;
+}
+
+// monster death callback
+fn monster_death(monster: Entity, game: &mut Game, world: &mut World) {
+    // transform it into a nasty corpse! it dosen't block, can't be attacked, and dosn't move
+    world.fighters.remove(monster);
+    world.ais.remove(monster);
+    world.packs.remove(monster);
+    let monster = &mut world.objects[monster];
+    game.messages
+        .add(game.turn, format!("{} is dead!", monster.name), ORANGE);
+    monster.char = '%';
+    monster.color = DARK_RED;
+    monster.blocks = false;
+    monster.name = format!("remains of {}", monster.name);
+}
@@ -0,0 +1,271 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use crate::map::{Map, TileKind};
+use crate::object::Object;
+use crate::world::Storage;
+
+/*********  CONSTANTS  **********/
+/// the cost of a step along a row or column
+const STEP_COST: u32 = 10;
+/// the cost of a diagonal step, roughly sqrt(2) times a straight one
+const DIAGONAL_COST: u32 = 14;
+/// What it costs to go through a tile taken by something that blocks, such as
+/// another monster. It might have moved by the time we get there, so it isn't
+/// a wall, but it's worth a fair detour to go around.
+const BLOCKING_OBJECT_COST: u32 = 12 * STEP_COST;
+/// what it costs to go through a closed door: a turn to open it, then the step
+const CLOSED_DOOR_COST: u32 = STEP_COST;
+/// every direction a step can be taken in
+pub const DIRECTIONS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/*********  STRUCTURES  *********/
+/// A tile waiting to be looked at, ordered so the `BinaryHeap` hands out the
+/// one with the lowest estimated total cost first
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Open {
+    estimate: u32,
+    cost: u32,
+    index: usize,
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, to make the max-heap a min-heap
+        other
+            .estimate
+            .cmp(&self.estimate)
+            .then_with(|| self.cost.cmp(&other.cost))
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Find the cheapest path from `start` to `goal` with A*, moving in any of the
+/// eight directions. Walls can't be crossed, though closed doors can, since
+/// walking into one opens it; tiles with a blocking object on
+/// them can, at a high cost, so a crowd is walked around when there's room and
+/// queued behind when there isn't.
+///
+/// Returns the steps to take, not including `start` but ending at `goal`, or
+/// `None` if the goal can't be reached at all.
+pub fn find_path(
+    map: &Map,
+    objects: &Storage<Object>,
+    start: (i32, i32),
+    goal: (i32, i32),
+) -> Option<Vec<(i32, i32)>> {
+    let width = map.len() as i32;
+    let height = map.first().map_or(0, |column| column.len()) as i32;
+    let in_map = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < width && y < height;
+    if !in_map(start) || !in_map(goal) {
+        return None;
+    }
+    let index = |(x, y): (i32, i32)| (y * width + x) as usize;
+    let pos = |index: usize| (index as i32 % width, index as i32 / width);
+
+    // the tiles that cost extra because something is standing on them
+    let mut occupied = vec![false; (width * height) as usize];
+    for object in objects.values().filter(|object| object.blocks) {
+        if in_map(object.pos()) {
+            occupied[index(object.pos())] = true;
+        }
+    }
+
+    let mut cost_so_far = vec![u32::MAX; occupied.len()];
+    let mut came_from = vec![usize::MAX; occupied.len()];
+    let mut open = BinaryHeap::new();
+    cost_so_far[index(start)] = 0;
+    open.push(Open {
+        estimate: heuristic(start, goal),
+        cost: 0,
+        index: index(start),
+    });
+
+    while let Some(Open {
+        cost,
+        index: current,
+        ..
+    }) = open.pop()
+    {
+        if current == index(goal) {
+            // walk back from the goal to build the path
+            let mut path = vec![];
+            let mut step = current;
+            while step != index(start) {
+                path.push(pos(step));
+                step = came_from[step];
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost > cost_so_far[current] {
+            // there's already been a cheaper way here
+            continue;
+        }
+
+        let (x, y) = pos(current);
+        for &(dx, dy) in &DIRECTIONS {
+            let next = (x + dx, y + dy);
+            if !in_map(next) {
+                continue;
+            }
+            let kind = map[next.0 as usize][next.1 as usize].kind;
+            // closed doors are opened on the way
+            if kind.blocks_movement() && kind != TileKind::ClosedDoor {
+                continue;
+            }
+            let step_cost = if dx != 0 && dy != 0 {
+                DIAGONAL_COST
+            } else {
+                STEP_COST
+            };
+            let mut next_cost = cost + step_cost;
+            if kind == TileKind::ClosedDoor {
+                next_cost += CLOSED_DOOR_COST;
+            }
+            // the goal itself is usually taken, by whatever is being chased
+            if next != goal && occupied[index(next)] {
+                next_cost += BLOCKING_OBJECT_COST;
+            }
+            if next_cost < cost_so_far[index(next)] {
+                cost_so_far[index(next)] = next_cost;
+                came_from[index(next)] = current;
+                open.push(Open {
+                    estimate: next_cost + heuristic(next, goal),
+                    cost: next_cost,
+                    index: index(next),
+                });
+            }
+        }
+    }
+    None
+}
+
+/// the cheapest the rest of the way could be, if nothing was in the way
+fn heuristic((x1, y1): (i32, i32), (x2, y2): (i32, i32)) -> u32 {
+    let dx = (x1 - x2).unsigned_abs();
+    let dy = (y1 - y2).unsigned_abs();
+    let (straight, diagonal) = (dx.max(dy) - dx.min(dy), dx.min(dy));
+    straight * STEP_COST + diagonal * DIAGONAL_COST
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::WHITE;
+    use crate::map::Tile;
+    use crate::world::{Bundle, World};
+
+    /// A map drawn as rows of text: `#` walls, `+` closed doors, `o` an orc
+    /// standing on the floor, anything else floor. Returns the map, and a
+    /// world with the orcs in it.
+    fn parse(rows: &[&str]) -> (Map, World) {
+        let mut map = vec![vec![Tile::empty(); rows.len()]; rows[0].len()];
+        // the player stays out of the way
+        let mut world = World::new(Bundle::new(Object::new(
+            -1, -1, '@', WHITE, false, "player",
+        )));
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                map[x][y].kind = match c {
+                    '#' => TileKind::Wall,
+                    '+' => TileKind::ClosedDoor,
+                    _ => TileKind::Floor,
+                };
+                if c == 'o' {
+                    let orc = Object::new(x as i32, y as i32, 'o', WHITE, true, "orc");
+                    world.spawn(Bundle::new(orc));
+                }
+            }
+        }
+        (map, world)
+    }
+
+    #[test]
+    fn straight_paths_go_straight() {
+        let (map, world) = parse(&["......"]);
+        assert_eq!(
+            find_path(&map, &world.objects, (0, 0), (4, 0)),
+            Some(vec![(1, 0), (2, 0), (3, 0), (4, 0)])
+        );
+        assert_eq!(
+            find_path(&map, &world.objects, (2, 0), (2, 0)),
+            Some(vec![])
+        );
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let (map, world) = parse(&[
+            ".....", //
+            ".###.", //
+            "..#..", //
+        ]);
+        let path = find_path(&map, &world.objects, (1, 2), (3, 2)).unwrap();
+        assert_eq!(path, vec![(0, 1), (1, 0), (2, 0), (3, 0), (4, 1), (3, 2)]);
+    }
+
+    #[test]
+    fn paths_go_through_closed_doors() {
+        let (map, world) = parse(&[
+            "..#..", //
+            "..+..", //
+            "..#..", //
+        ]);
+        let path = find_path(&map, &world.objects, (0, 1), (4, 1)).unwrap();
+        assert_eq!(path, vec![(1, 1), (2, 1), (3, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn occupied_tiles_are_walked_around() {
+        let (map, world) = parse(&[
+            "...", //
+            ".o.", //
+            "...", //
+        ]);
+        let path = find_path(&map, &world.objects, (1, 0), (1, 2)).unwrap();
+        // two diagonal steps, either side of the orc
+        assert_eq!(path.len(), 2);
+        assert!(!path.contains(&(1, 1)));
+    }
+
+    #[test]
+    fn occupied_tiles_are_queued_behind_if_there_is_no_way_round() {
+        let (map, world) = parse(&[
+            "#####", //
+            "..o..", //
+            "#####", //
+        ]);
+        let path = find_path(&map, &world.objects, (0, 1), (4, 1)).unwrap();
+        assert_eq!(path, vec![(1, 1), (2, 1), (3, 1), (4, 1)]);
+        // and whatever is being chased doesn't count as in the way
+        let path = find_path(&map, &world.objects, (0, 1), (2, 1)).unwrap();
+        assert_eq!(path, vec![(1, 1), (2, 1)]);
+    }
+
+    #[test]
+    fn walled_off_goals_have_no_path() {
+        let (map, world) = parse(&[
+            "..#..", //
+            "..#..", //
+            "..#..", //
+        ]);
+        assert_eq!(find_path(&map, &world.objects, (0, 1), (4, 1)), None);
+        assert_eq!(find_path(&map, &world.objects, (0, 1), (9, 1)), None);
+    }
+}
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::game::{initialise_fov, update_fov, Game};
+use crate::map::{MAP_HEIGHT, MAP_WIDTH};
+use crate::world::World;
+
+/*********  CONSTANTS  **********/
+/// Bump this whenever a change to the game's structures means older save files
+/// can no longer be read.
+pub const SAVE_VERSION: u32 = 14;
+
+/*********  STRUCTURES  *********/
+/// Everything needed to resume a game, as it is written to disk
+#[derive(Serialize)]
+struct SaveFile<'a> {
+    version: u32,
+    game: &'a Game,
+    world: &'a World,
+}
+
+/// The same file read back in
+#[derive(Deserialize)]
+struct LoadedSave {
+    game: Game,
+    world: World,
+}
+
+/// Just enough of a save file to know whether the rest can be read
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+/// Reasons a game could not be saved or loaded
+#[derive(Debug)]
+pub enum SaveError {
+    /// the file couldn't be read or written
+    Io(io::Error),
+    /// the file isn't a save file, or it has been damaged
+    Corrupt(serde_json::Error),
+    /// the file was written by a different version of the game
+    Incompatible { version: u32 },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access the save file: {}", err),
+            SaveError::Corrupt(err) => write!(f, "the save file is corrupt: {}", err),
+            SaveError::Incompatible { version } => write!(
+                f,
+                "the save file is from an incompatible version of the game \
+                 (save version {}, expected {})",
+                version, SAVE_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaveError::Io(err) => Some(err),
+            SaveError::Corrupt(err) => Some(err),
+            SaveError::Incompatible { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for SaveError {
+    fn from(err: io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(err: serde_json::Error) -> Self {
+        SaveError::Corrupt(err)
+    }
+}
+
+/// Write the game to `path`. The file is written next to the old one first and
+/// then moved over it, so a failed save never destroys the previous one.
+pub fn save_game(path: &Path, game: &Game, world: &World) -> Result<(), SaveError> {
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        game,
+        world,
+    };
+    let data = serde_json::to_string(&save)?;
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Read a game back from `path`, rebuilding everything that isn't saved
+pub fn load_game(path: &Path) -> Result<(Game, World), SaveError> {
+    let data = fs::read_to_string(path)?;
+
+    // check the version first, so an old save gives a useful error rather than
+    // complaining about whichever field happens to have changed
+    let header: SaveHeader = serde_json::from_str(&data)?;
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::Incompatible {
+            version: header.version,
+        });
+    }
+
+    let LoadedSave { mut game, world } = serde_json::from_str(&data)?;
+    check_consistent(&game, &world)?;
+    initialise_fov(&mut game);
+    update_fov(&mut game, &world);
+    Ok((game, world))
+}
+
+/// Make sure a save that parsed is also something the game can run, so a
+/// hand-edited or truncated file can't cause a panic later on
+fn check_consistent(game: &Game, world: &World) -> Result<(), SaveError> {
+    use serde::de::Error as _;
+
+    let corrupt = |message: &str| SaveError::Corrupt(serde_json::Error::custom(message));
+    let in_map = |(x, y): (i32, i32)| x >= 0 && y >= 0 && x < MAP_WIDTH && y < MAP_HEIGHT;
+
+    if game.map.len() != MAP_WIDTH as usize
+        || game
+            .map
+            .iter()
+            .any(|column| column.len() != MAP_HEIGHT as usize)
+    {
+        return Err(corrupt("the map is the wrong size"));
+    }
+    if !world.is_consistent() {
+        return Err(corrupt("the components don't match the objects"));
+    }
+    if world.objects.get(world.player).is_none() || !world.fighters.has(world.player) {
+        return Err(corrupt("there is no player"));
+    }
+    if !world.objects.values().all(|object| in_map(object.pos())) {
+        return Err(corrupt("an object is outside the map"));
+    }
+    // anything that never gains energy would stall the turn queue for good
+    if world.fighters.values().any(|fighter| fighter.speed <= 0) {
+        return Err(corrupt("a fighter's speed isn't above 0"));
+    }
+    if let Err(err) = game.spawns.validate() {
+        return Err(corrupt(&err.to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::new_game;
+    use crate::spawns::SpawnTable;
+    use std::path::PathBuf;
+
+    /// somewhere for a test to save to, different for each test
+    fn save_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("roguelike-{}-{}", std::process::id(), name))
+    }
+
+    /// save a new game, let `edit` change the file's JSON, then load it again
+    fn load_edited(name: &str, edit: impl FnOnce(&mut serde_json::Value)) -> Result<(), SaveError> {
+        let path = save_path(name);
+        let (game, world) = new_game(1, SpawnTable::default());
+        save_game(&path, &game, &world).unwrap();
+        let mut json: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        edit(&mut json);
+        fs::write(&path, json.to_string()).unwrap();
+        let result = load_game(&path).map(|_| ());
+        fs::remove_file(&path).unwrap();
+        result
+    }
+
+    fn corrupt_message(result: Result<(), SaveError>) -> String {
+        match result {
+            Err(SaveError::Corrupt(err)) => err.to_string(),
+            other => panic!("expected a corrupt save, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_saved_game_loads_as_it_was() {
+        let path = save_path("round-trip");
+        let (mut game, mut world) = new_game(7, SpawnTable::default());
+        let player = world.player;
+        world.objects[player].set_pos(3, 4);
+        world.fighters[player].hp = 12;
+        game.turn = 99;
+        save_game(&path, &game, &world).unwrap();
+        let (loaded_game, loaded_world) = load_game(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded_game.seed, 7);
+        assert_eq!(loaded_game.turn, 99);
+        assert_eq!(loaded_world.player, player);
+        assert_eq!(loaded_world.objects[player].pos(), (3, 4));
+        assert_eq!(loaded_world.fighters[player].hp, 12);
+        assert_eq!(loaded_world.len(), world.len());
+        // and it goes on the same way, too
+        assert_eq!(
+            serde_json::to_string(&loaded_game.rng).unwrap(),
+            serde_json::to_string(&game.rng).unwrap()
+        );
+    }
+
+    #[test]
+    fn a_missing_file_is_an_io_error() {
+        let result = load_game(&save_path("missing"));
+        assert!(
+            matches!(result, Err(SaveError::Io(_))),
+            "{:?}",
+            result.err()
+        );
+    }
+
+    #[test]
+    fn garbage_and_truncated_files_are_corrupt() {
+        let path = save_path("truncated");
+        let (game, world) = new_game(1, SpawnTable::default());
+        save_game(&path, &game, &world).unwrap();
+        let data = fs::read_to_string(&path).unwrap();
+        let only_the_version = format!("{{\"version\": {}", SAVE_VERSION);
+
+        for broken in ["", "not a save", &only_the_version, &data[..data.len() / 2]] {
+            fs::write(&path, broken).unwrap();
+            let result = load_game(&path);
+            assert!(
+                matches!(result, Err(SaveError::Corrupt(_))),
+                "{:?}",
+                result.err()
+            );
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn other_versions_are_incompatible() {
+        let result = load_edited("version", |json| {
+            json["version"] = (SAVE_VERSION - 1).into();
+        });
+        match result {
+            Err(SaveError::Incompatible { version }) => assert_eq!(version, SAVE_VERSION - 1),
+            other => panic!("expected an incompatible save, not {:?}", other),
+        }
+    }
+
+    #[test]
+    fn a_save_without_its_player_is_corrupt() {
+        let result = load_edited("no-player", |json| {
+            json["world"]["objects"][0] = serde_json::Value::Null;
+        });
+        assert_eq!(
+            corrupt_message(result),
+            "the components don't match the objects"
+        );
+    }
+
+    #[test]
+    fn objects_off_the_map_are_corrupt() {
+        let result = load_edited("off-map", |json| {
+            json["world"]["objects"][0][1]["x"] = (-1).into();
+        });
+        assert_eq!(corrupt_message(result), "an object is outside the map");
+    }
+
+    #[test]
+    fn a_player_that_never_gets_a_turn_is_corrupt() {
+        let result = load_edited("speed", |json| {
+            // the player is the first entity
+            json["world"]["fighters"][0][1]["speed"] = 0.into();
+        });
+        assert_eq!(corrupt_message(result), "a fighter's speed isn't above 0");
+    }
+
+    #[test]
+    fn the_saved_spawn_table_is_checked_too() {
+        let result = load_edited("spawns", |json| {
+            json["game"]["spawns"]["monsters"][0]["speed"] = 0.into();
+        });
+        assert_eq!(
+            corrupt_message(result),
+            "bad entry in the spawn table (monster \"orc\"): speed must be above 0, not 0"
+        );
+    }
+}
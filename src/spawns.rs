@@ -0,0 +1,603 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::colors::Color;
+use crate::effects::StatusEffect;
+use crate::game::{GameRng, NORMAL_SPEED, RANGED_MIN_DISTANCE};
+use crate::mapgen::MapStyle;
+use crate::object::{Ai, DeathCallback, Fighter, Object};
+use crate::world::Bundle;
+
+/*********  CONSTANTS  **********/
+/// The spawn table the game ships with, used when no other one is given
+const DEFAULT_SPAWNS: &str = include_str!("../data/monsters.ron");
+
+/*********  STRUCTURES  *********/
+/// What can spawn in the dungeon and how often, as read from a data file such
+/// as `data/monsters.ron`
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpawnTable {
+    /// the most monsters a single room can hold, by depth
+    pub max_per_room: Vec<ByDepth>,
+    /// how levels are dug, by depth: one of the styles is picked at random
+    #[serde(default = "default_generators")]
+    pub generators: Vec<ByDepth<Vec<MapStyle>>>,
+    pub monsters: Vec<MonsterTemplate>,
+}
+
+/// A value that applies to the dungeon levels `from` to `to` (inclusive). With
+/// no `to` it applies to every level from `from` on.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ByDepth<T = u32> {
+    pub from: u32,
+    #[serde(default)]
+    pub to: Option<u32>,
+    pub value: T,
+}
+
+/// Everything needed to make one kind of monster
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MonsterTemplate {
+    pub name: String,
+    pub glyph: char,
+    pub color: Color,
+    pub hp: i32,
+    pub defense: i32,
+    pub power: i32,
+    /// what killing it is worth to the player
+    pub xp: i32,
+    /// the energy it gains each tick; the player's is `NORMAL_SPEED`
+    #[serde(default = "normal_speed")]
+    pub speed: i32,
+    pub ai: Ai,
+    pub on_death: DeathCallback,
+    /// a status effect its hits put on whoever they hurt
+    #[serde(default)]
+    pub on_hit: Option<StatusEffect>,
+    /// if given, it turns up in a pack of this many, which hunt together
+    #[serde(default)]
+    pub pack: Option<PackSize>,
+    /// how likely this monster is to be picked, relative to the others, by depth
+    pub weights: Vec<ByDepth>,
+}
+
+/// How many monsters of a kind spawn together, from `min` to `max`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PackSize {
+    pub min: u32,
+    pub max: u32,
+}
+
+/// Reasons a spawn table could not be loaded
+#[derive(Debug)]
+pub enum SpawnError {
+    /// the file couldn't be read
+    Io(io::Error),
+    /// the file isn't valid RON, or doesn't have the expected fields
+    Parse(ron::error::SpannedError),
+    /// the file parsed, but something in it makes no sense
+    Invalid { entry: String, reason: String },
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpawnError::Io(err) => write!(f, "could not read the spawn table: {}", err),
+            SpawnError::Parse(err) => write!(f, "the spawn table could not be parsed: {}", err),
+            SpawnError::Invalid { entry, reason } => {
+                write!(f, "bad entry in the spawn table ({}): {}", entry, reason)
+            }
+        }
+    }
+}
+
+impl Error for SpawnError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpawnError::Io(err) => Some(err),
+            SpawnError::Parse(err) => Some(err),
+            SpawnError::Invalid { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for SpawnError {
+    fn from(err: io::Error) -> Self {
+        SpawnError::Io(err)
+    }
+}
+
+impl From<ron::error::SpannedError> for SpawnError {
+    fn from(err: ron::error::SpannedError) -> Self {
+        SpawnError::Parse(err)
+    }
+}
+
+impl SpawnTable {
+    /// Read and validate a spawn table from a RON file
+    pub fn load(path: &Path) -> Result<Self, SpawnError> {
+        let data = fs::read_to_string(path)?;
+        SpawnTable::parse(&data)
+    }
+
+    /// Parse and validate a spawn table from RON text
+    pub fn parse(data: &str) -> Result<Self, SpawnError> {
+        // optional fields like `to` can be written without wrapping them in `Some`
+        let options = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME);
+        let table: SpawnTable = options.from_str(data)?;
+        table.validate()?;
+        Ok(table)
+    }
+
+    /// Check everything serde can't: stats that make sense, no duplicate
+    /// monsters, and depth ranges that don't contradict each other
+    pub fn validate(&self) -> Result<(), SpawnError> {
+        let invalid = |entry: &str, reason: String| SpawnError::Invalid {
+            entry: entry.into(),
+            reason,
+        };
+
+        check_depths(&self.max_per_room).map_err(|reason| invalid("max_per_room", reason))?;
+        check_depths(&self.generators).map_err(|reason| invalid("generators", reason))?;
+        for range in &self.generators {
+            if range.value.is_empty() {
+                return Err(invalid(
+                    "generators",
+                    format!("the range starting at {} has no styles", range.from),
+                ));
+            }
+        }
+        if let Some(level) = first_gap(&self.generators) {
+            return Err(invalid(
+                "generators",
+                format!("there is no style for level {}", level),
+            ));
+        }
+        if self.monsters.is_empty() {
+            return Err(invalid("monsters", "there are no monsters".into()));
+        }
+
+        let mut names = HashSet::new();
+        for (index, monster) in self.monsters.iter().enumerate() {
+            let entry = if monster.name.trim().is_empty() {
+                format!("monster #{}", index + 1)
+            } else {
+                format!("monster \"{}\"", monster.name)
+            };
+            let entry = entry.as_str();
+
+            if monster.name.trim().is_empty() {
+                return Err(invalid(entry, "the name is empty".into()));
+            }
+            if !names.insert(monster.name.as_str()) {
+                return Err(invalid(
+                    entry,
+                    "there is already a monster with this name".into(),
+                ));
+            }
+            if monster.hp <= 0 {
+                return Err(invalid(
+                    entry,
+                    format!("hp must be above 0, not {}", monster.hp),
+                ));
+            }
+            if monster.speed <= 0 {
+                return Err(invalid(
+                    entry,
+                    format!("speed must be above 0, not {}", monster.speed),
+                ));
+            }
+            for &(stat, value) in &[
+                ("defense", monster.defense),
+                ("power", monster.power),
+                ("xp", monster.xp),
+            ] {
+                if value < 0 {
+                    return Err(invalid(
+                        entry,
+                        format!("{} can't be negative ({})", stat, value),
+                    ));
+                }
+            }
+            match monster.ai {
+                Ai::Basic => {}
+                Ai::Ranged { range } if range < RANGED_MIN_DISTANCE => {
+                    return Err(invalid(
+                        entry,
+                        format!(
+                            "a ranged monster's range must be at least {}, not {}",
+                            RANGED_MIN_DISTANCE, range
+                        ),
+                    ));
+                }
+                Ai::Ranged { .. } => {}
+                Ai::Fleeing { .. } | Ai::Confused { .. } => {
+                    return Err(invalid(
+                        entry,
+                        "monsters must start out as Basic or Ranged".into(),
+                    ));
+                }
+            }
+            if let Some(effect) = monster.on_hit {
+                if effect.turns <= 0 {
+                    return Err(invalid(
+                        entry,
+                        format!("on_hit must last at least 1 turn, not {}", effect.turns),
+                    ));
+                }
+                if effect.magnitude < 0 {
+                    return Err(invalid(
+                        entry,
+                        format!(
+                            "on_hit's magnitude can't be negative ({})",
+                            effect.magnitude
+                        ),
+                    ));
+                }
+            }
+            if let Some(pack) = monster.pack {
+                if pack.min == 0 {
+                    return Err(invalid(entry, "a pack needs at least 1 monster".into()));
+                }
+                if pack.max < pack.min {
+                    return Err(invalid(
+                        entry,
+                        format!(
+                            "a pack's max ({}) can't be less than its min ({})",
+                            pack.max, pack.min
+                        ),
+                    ));
+                }
+            }
+            if monster.on_death != DeathCallback::Monster {
+                return Err(invalid(entry, "on_death must be Monster".into()));
+            }
+            if monster.weights.is_empty() {
+                return Err(invalid(
+                    entry,
+                    "it has no weights, so it would never spawn".into(),
+                ));
+            }
+            check_depths(&monster.weights)
+                .map_err(|reason| invalid(entry, format!("in weights: {}", reason)))?;
+        }
+        Ok(())
+    }
+
+    /// how many monsters a room can hold at this depth
+    pub fn max_per_room(&self, level: u32) -> u32 {
+        value_at(&self.max_per_room, level)
+    }
+
+    /// Pick how a level at this depth is dug. A range with a single style
+    /// always uses it, without touching `rng`.
+    pub fn choose_map_style(&self, level: u32, rng: &mut GameRng) -> MapStyle {
+        let styles = range_at(&self.generators, level).map_or(&[][..], |range| &range.value[..]);
+        match styles {
+            [style] => *style,
+            // validation makes sure every level has a style
+            _ => *styles.choose(rng).unwrap_or(&MapStyle::RoomsAndCorridors),
+        }
+    }
+
+    /// Pick a monster for this depth, each as likely as its weight there.
+    /// Returns `None` if nothing can spawn this deep.
+    pub fn choose_monster(&self, level: u32, rng: &mut GameRng) -> Option<&MonsterTemplate> {
+        let weights = self
+            .monsters
+            .iter()
+            .map(|monster| value_at(&monster.weights, level));
+        // this only fails if every weight is 0
+        let index = WeightedIndex::new(weights).ok()?;
+        Some(&self.monsters[index.sample(rng)])
+    }
+}
+
+impl Default for SpawnTable {
+    /// the spawn table from `data/monsters.ron`, as it was when the game was built
+    fn default() -> Self {
+        SpawnTable::parse(DEFAULT_SPAWNS).expect("the built-in spawn table is invalid")
+    }
+}
+
+impl MonsterTemplate {
+    /// make a live monster of this kind at (x, y)
+    pub fn spawn(&self, x: i32, y: i32) -> Bundle {
+        let mut monster = Bundle::new(Object::new(x, y, self.glyph, self.color, true, &self.name));
+        monster.object.alive = true;
+        monster.fighter = Some(Fighter {
+            base_max_hp: self.hp,
+            hp: self.hp,
+            base_defense: self.defense,
+            base_power: self.power,
+            xp: self.xp,
+            on_death: self.on_death,
+            effects: vec![],
+            on_hit: self.on_hit,
+            speed: self.speed,
+            energy: 0,
+        });
+        monster.ai = Some(self.ai.clone());
+        monster
+    }
+}
+
+fn normal_speed() -> i32 {
+    NORMAL_SPEED
+}
+
+/// one style for each of the first four levels to show them off; deeper ones
+/// are a surprise
+fn default_generators() -> Vec<ByDepth<Vec<MapStyle>>> {
+    let styles = [
+        MapStyle::RoomsAndCorridors,
+        MapStyle::Bsp,
+        MapStyle::Caves,
+        MapStyle::DrunkardsWalk,
+    ];
+    let mut generators: Vec<_> = (1..)
+        .zip(styles)
+        .map(|(level, style)| ByDepth {
+            from: level,
+            to: Some(level),
+            value: vec![style],
+        })
+        .collect();
+    generators.push(ByDepth {
+        from: 5,
+        to: None,
+        value: styles.to_vec(),
+    });
+    generators
+}
+
+/// whichever range covers `level`, if any
+fn range_at<T>(table: &[ByDepth<T>], level: u32) -> Option<&ByDepth<T>> {
+    table
+        .iter()
+        .find(|range| level >= range.from && range.to.is_none_or(|to| level <= to))
+}
+
+/// the value of whichever range covers `level`, or 0 if none do
+fn value_at(table: &[ByDepth], level: u32) -> u32 {
+    range_at(table, level).map_or(0, |range| range.value)
+}
+
+/// the shallowest level no range covers, if there is one
+fn first_gap<T>(table: &[ByDepth<T>]) -> Option<u32> {
+    let mut level = 1;
+    while let Some(range) = range_at(table, level) {
+        level = range.to? + 1;
+    }
+    Some(level)
+}
+
+/// make sure each range starts at level 1 or deeper, ends no earlier than it
+/// starts, and doesn't overlap any other
+fn check_depths<T>(table: &[ByDepth<T>]) -> Result<(), String> {
+    for range in table {
+        if range.from == 0 {
+            return Err("depths start at 1, not 0".into());
+        }
+        if let Some(to) = range.to {
+            if to < range.from {
+                return Err(format!(
+                    "the range {}..{} ends before it starts",
+                    range.from, to
+                ));
+            }
+        }
+    }
+    for (i, first) in table.iter().enumerate() {
+        for second in &table[i + 1..] {
+            let overlaps = first.to.is_none_or(|to| second.from <= to)
+                && second.to.is_none_or(|to| first.from <= to);
+            if overlaps {
+                return Err(format!(
+                    "the ranges starting at {} and {} overlap",
+                    first.from, second.from
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// a table with a single monster, its fields replaced by those in `fields`
+    fn table(max_per_room: &str, fields: &[(&str, &str)]) -> String {
+        let mut monster = vec![
+            ("name", "\"orc\""),
+            ("glyph", "'o'"),
+            ("color", "(r: 63, g: 127, b: 63)"),
+            ("hp", "10"),
+            ("defense", "0"),
+            ("power", "3"),
+            ("xp", "35"),
+            ("ai", "Basic"),
+            ("on_death", "Monster"),
+            ("weights", "[(from: 1, value: 80)]"),
+        ];
+        for &(field, value) in fields {
+            match monster.iter_mut().find(|(name, _)| *name == field) {
+                Some(entry) => entry.1 = value,
+                None => monster.push((field, value)),
+            }
+        }
+        let monster: Vec<_> = monster
+            .iter()
+            .map(|(field, value)| format!("{}: {}", field, value))
+            .collect();
+        format!(
+            "(max_per_room: {}, monsters: [({})])",
+            max_per_room,
+            monster.join(", ")
+        )
+    }
+
+    /// the single monster table, with `generators` added
+    fn with_generators(generators: &str) -> String {
+        table(ROOMS, &[]).replacen(
+            "monsters:",
+            &format!("generators: {}, monsters:", generators),
+            1,
+        )
+    }
+
+    fn error(data: &str) -> String {
+        SpawnTable::parse(data).unwrap_err().to_string()
+    }
+
+    const ROOMS: &str = "[(from: 1, value: 2)]";
+
+    #[test]
+    fn the_built_in_table_is_valid() {
+        SpawnTable::parse(DEFAULT_SPAWNS).unwrap();
+        SpawnTable::parse(&table(ROOMS, &[])).unwrap();
+    }
+
+    #[test]
+    fn depth_ranges_must_be_in_order() {
+        assert_eq!(
+            error(&table("[(from: 4, to: 2, value: 2)]", &[])),
+            "bad entry in the spawn table (max_per_room): the range 4..2 ends before it starts"
+        );
+        assert_eq!(
+            error(&table(ROOMS, &[("weights", "[(from: 0, value: 1)]")])),
+            "bad entry in the spawn table (monster \"orc\"): in weights: depths start at 1, not 0"
+        );
+    }
+
+    #[test]
+    fn depth_ranges_cant_overlap() {
+        assert_eq!(
+            error(&table("[(from: 1, value: 2), (from: 1, value: 3)]", &[])),
+            "bad entry in the spawn table (max_per_room): the ranges starting at 1 and 1 overlap"
+        );
+        assert_eq!(
+            error(&table(
+                ROOMS,
+                &[(
+                    "weights",
+                    "[(from: 3, value: 1), (from: 1, to: 4, value: 2)]"
+                )]
+            )),
+            "bad entry in the spawn table (monster \"orc\"): in weights: \
+             the ranges starting at 3 and 1 overlap"
+        );
+    }
+
+    #[test]
+    fn unknown_ais_are_rejected() {
+        let err = SpawnTable::parse(&table(ROOMS, &[("ai", "Sneaky")])).unwrap_err();
+        assert!(matches!(err, SpawnError::Parse(_)), "{:?}", err);
+        assert!(err.to_string().contains("Sneaky"), "{}", err);
+
+        assert_eq!(
+            error(&table(ROOMS, &[("ai", "Fleeing(previous_ai: Basic)")])),
+            "bad entry in the spawn table (monster \"orc\"): \
+             monsters must start out as Basic or Ranged"
+        );
+        assert_eq!(
+            error(&table(ROOMS, &[("ai", "Ranged(range: 1)")])),
+            "bad entry in the spawn table (monster \"orc\"): \
+             a ranged monster's range must be at least 3, not 1"
+        );
+    }
+
+    #[test]
+    fn speed_must_be_above_zero() {
+        for speed in ["0", "-5"] {
+            assert_eq!(
+                error(&table(ROOMS, &[("speed", speed)])),
+                format!(
+                    "bad entry in the spawn table (monster \"orc\"): speed must be above 0, not {}",
+                    speed
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn packs_need_a_sensible_size() {
+        assert_eq!(
+            error(&table(ROOMS, &[("pack", "(min: 3, max: 2)")])),
+            "bad entry in the spawn table (monster \"orc\"): \
+             a pack's max (2) can't be less than its min (3)"
+        );
+    }
+
+    #[test]
+    fn every_level_needs_a_map_style() {
+        assert_eq!(
+            error(&with_generators("[(from: 2, value: [Caves])]")),
+            "bad entry in the spawn table (generators): there is no style for level 1"
+        );
+        assert_eq!(
+            error(&with_generators("[(from: 1, to: 3, value: [Caves])]")),
+            "bad entry in the spawn table (generators): there is no style for level 4"
+        );
+        assert_eq!(
+            error(&with_generators("[(from: 1, value: [])]")),
+            "bad entry in the spawn table (generators): the range starting at 1 has no styles"
+        );
+    }
+
+    #[test]
+    fn map_styles_are_picked_by_depth() {
+        let spawns = SpawnTable::parse(DEFAULT_SPAWNS).unwrap();
+        assert_eq!(spawns.generators, default_generators());
+        let mut rng = GameRng::seed_from_u64(0);
+        let styles: Vec<_> = (1..=4)
+            .map(|level| spawns.choose_map_style(level, &mut rng))
+            .collect();
+        assert_eq!(
+            styles,
+            [
+                MapStyle::RoomsAndCorridors,
+                MapStyle::Bsp,
+                MapStyle::Caves,
+                MapStyle::DrunkardsWalk
+            ]
+        );
+
+        let caves = SpawnTable::parse(&with_generators("[(from: 1, value: [Caves])]")).unwrap();
+        for level in [1, 5, 50] {
+            assert_eq!(caves.choose_map_style(level, &mut rng), MapStyle::Caves);
+        }
+    }
+
+    #[test]
+    fn malformed_ron_is_a_parse_error() {
+        for data in [
+            "",
+            "(max_per_room: [",
+            "(max_per_room: [], monsters: [], extra: 1)",
+        ] {
+            let err = SpawnTable::parse(data).unwrap_err();
+            assert!(matches!(err, SpawnError::Parse(_)), "{:?}", err);
+            assert!(
+                err.to_string()
+                    .starts_with("the spawn table could not be parsed: "),
+                "{}",
+                err
+            );
+        }
+    }
+}
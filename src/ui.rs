@@ -0,0 +1,764 @@
+//! The game's screens: the title menu, the map and status panel, and the
+//! menus over them. They are drawn on any `Console` and driven by any `Input`,
+//! so every frontend looks and plays the same.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::camera::Camera;
+use crate::colors::*;
+use crate::console::{wrap, Console, Event, Input, Mouse};
+use crate::fov::Line;
+use crate::game::*;
+use crate::items::*;
+use crate::keys::{Command, KeyBindings, Keypress};
+use crate::map::{MAP_HEIGHT, MAP_WIDTH};
+use crate::messages::Messages;
+use crate::object::Object;
+use crate::save::{load_game, save_game, SaveError};
+use crate::spawns::SpawnTable;
+use crate::world::{Bundle, World};
+
+/*********  CONSTANTS  **********/
+/// Actual size of the screen
+pub const SCREEN_WIDTH: i32 = 80;
+pub const SCREEN_HEIGHT: i32 = 50;
+/// Other settings
+pub const LIMIT_FPS: i32 = 20; // 20 frames-per-second maximum
+const SAVE_FILE: &str = "savegame";
+/// the monsters new games are filled with
+const SPAWN_FILE: &str = "data/monsters.ron";
+/// which key does what
+const KEYS_FILE: &str = "data/keybindings.ron";
+/// the status panel at the bottom of the screen
+const PANEL_HEIGHT: i32 = 6;
+const PANEL_Y: i32 = SCREEN_HEIGHT - PANEL_HEIGHT;
+/// the line between the map and the panel, naming whatever is under the mouse
+const NAMES_Y: i32 = PANEL_Y - 1;
+/// the part of the screen the map is shown in
+const VIEW_WIDTH: i32 = SCREEN_WIDTH;
+const VIEW_HEIGHT: i32 = NAMES_Y;
+const BAR_WIDTH: i32 = 28;
+/// the message log, to the right of the stats in the panel
+const MSG_X: i32 = BAR_WIDTH + 2;
+const MSG_WIDTH: i32 = SCREEN_WIDTH - BAR_WIDTH - 2;
+const MSG_HEIGHT: i32 = PANEL_HEIGHT - 1;
+/// width of the inventory menu
+const INVENTORY_WIDTH: i32 = 50;
+/// width of the level up menu
+const LEVEL_SCREEN_WIDTH: i32 = 40;
+/// how much a menu darkens what's behind it
+const MENU_BACKGROUND_ALPHA: f32 = 0.7;
+/// Colors
+const COLOR_TARGET_PATH: Color = Color::new(90, 140, 200);
+const COLOR_TARGET_AREA: Color = Color::new(220, 110, 40);
+
+/*********  STRUCTURES  *********/
+/// A frontend, and what the screens keep track of between frames
+pub struct Ui<B> {
+    pub backend: B,
+    /// the latest input, polled once a frame
+    key: Option<Keypress>,
+    mouse: Mouse,
+    /// the key bindings, loaded at startup
+    keys: KeyBindings,
+    /// which part of the map is on screen
+    camera: Camera,
+}
+
+/// What a game is started with, from the command line and the data files
+pub struct Setup {
+    /// the seed to play, if one was given
+    pub seed: Option<u64>,
+    pub spawns: SpawnTable,
+    pub keys: KeyBindings,
+}
+
+impl<B> Ui<B> {
+    pub fn new(backend: B, keys: KeyBindings) -> Self {
+        Ui {
+            backend,
+            key: None,
+            mouse: Mouse::default(),
+            keys,
+            camera: Camera::new(VIEW_WIDTH, VIEW_HEIGHT),
+        }
+    }
+
+    /// which part of the map was on screen in the last frame
+    pub fn camera(&self) -> Camera {
+        self.camera
+    }
+}
+
+/// Read the command line and the data files, before any frontend starts up, so
+/// mistakes in them are reported straight away. Exits if the game can't start.
+pub fn setup(args: impl Iterator<Item = String>) -> Setup {
+    // use the seed from the command line if one was given, so a run can be replayed
+    let seed = match parse_seed(args) {
+        Ok(seed) => seed,
+        Err(message) => {
+            eprintln!("{}", message);
+            std::process::exit(2);
+        }
+    };
+
+    let spawns = match SpawnTable::load(Path::new(SPAWN_FILE)) {
+        Ok(spawns) => spawns,
+        Err(err) => {
+            eprintln!("Could not load {}: {}", SPAWN_FILE, err);
+            std::process::exit(1);
+        }
+    };
+
+    // a bad key binding isn't worth stopping for, but the player should know
+    let (keys, warnings) = KeyBindings::load(Path::new(KEYS_FILE));
+    for warning in warnings {
+        eprintln!("Warning: {}: {}", KEYS_FILE, warning);
+    }
+
+    Setup { seed, spawns, keys }
+}
+
+/// The title screen: start a new game, continue the saved one, or quit
+pub fn main_menu<B: Console + Input>(ui: &mut Ui<B>, seed: Option<u64>, spawns: &SpawnTable) {
+    while !ui.backend.is_closed() {
+        // show the game's title
+        ui.backend.clear();
+        ui.backend.print_centered(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT / 2 - 4,
+            "TOMBS OF THE ANCIENT KINGS",
+            LIGHT_YELLOW,
+        );
+        ui.backend.print_centered(
+            SCREEN_WIDTH / 2,
+            SCREEN_HEIGHT - 2,
+            "By Sam Rust",
+            LIGHT_YELLOW,
+        );
+
+        // show options and wait for the player's choice
+        let choices = &["Play a new game", "Continue last game", "Quit"];
+        let choice = menu("", choices, 24, &mut ui.backend);
+
+        match choice {
+            Some(0) => {
+                // new game, from the command line seed if there was one
+                let (mut game, mut world) =
+                    new_game(seed.unwrap_or_else(rand::random), spawns.clone());
+                run_game(ui, &mut game, &mut world);
+            }
+            Some(1) => {
+                // load game
+                match load_game(Path::new(SAVE_FILE)) {
+                    Ok((mut game, mut world)) => run_game(ui, &mut game, &mut world),
+                    Err(SaveError::Io(ref err)) if err.kind() == io::ErrorKind::NotFound => {
+                        msgbox("\nNo saved game to load.\n", 24, &mut ui.backend);
+                    }
+                    Err(err) => {
+                        eprintln!("Could not load {}: {}", SAVE_FILE, err);
+                        msgbox(&format!("\n{}\n", err), 50, &mut ui.backend);
+                    }
+                }
+            }
+            Some(2) => {
+                // quit
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Play a game until it is over or the player leaves it, then either keep it
+/// for later or, if the player died, throw it away
+fn run_game<B: Console + Input>(ui: &mut Ui<B>, game: &mut Game, world: &mut World) {
+    play_game(ui, game, world);
+
+    let save_path = Path::new(SAVE_FILE);
+    if world.objects[world.player].alive {
+        if let Err(err) = save_game(save_path, game, world) {
+            eprintln!("Could not save the game: {}", err);
+            msgbox(&format!("\n{}\n", err), 50, &mut ui.backend);
+        }
+    } else {
+        if save_path.exists() {
+            if let Err(err) = fs::remove_file(save_path) {
+                eprintln!("Could not remove the old save file: {}", err);
+            }
+        }
+        game_over(ui, game);
+    }
+}
+
+/// Tell the player the run is over, then go back to the menu
+fn game_over<B: Console + Input>(ui: &mut Ui<B>, game: &Game) {
+    if ui.backend.is_closed() {
+        return;
+    }
+    let text = format!(
+        "\nYou died on turn {}.\n\nPress any key to return to the menu.\n",
+        game.turn
+    );
+    msgbox(&text, 40, &mut ui.backend);
+}
+
+/// Run the game until the player quits, dies or closes the window
+fn play_game<B: Console + Input>(ui: &mut Ui<B>, game: &mut Game, world: &mut World) {
+    while !ui.backend.is_closed() {
+        // check for input without waiting, so the screen keeps up with the mouse
+        ui.key = None;
+        match ui.backend.check_for_event() {
+            Some(Event::Mouse(mouse)) => ui.mouse = mouse,
+            Some(Event::Key(key)) => ui.key = Some(key),
+            None => {}
+        }
+
+        // render the screen
+        render_all(ui, game, world);
+        ui.backend.flush();
+
+        // the game is over once the player is dead; the frame above shows how
+        if !world.objects[world.player].alive {
+            break;
+        }
+
+        // Key handleing w/ exit
+        let player_action = handle_keys(ui, game, world);
+        if player_action == PlayerAction::Exit {
+            break;
+        }
+
+        // let everyone else act, until it's the player's turn again
+        if player_action == PlayerAction::TookTurn {
+            end_player_turn(game, world);
+        }
+
+        // let the player spend any experience they've earned
+        while world.objects[world.player].alive && can_level_up(world) && !ui.backend.is_closed() {
+            match level_up_menu(ui, game, world) {
+                Some(choice) => level_up(choice, game, world),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Ask the player which stat to raise for their new level. There's no
+/// cancelling; the menu stays up until they pick one, or close the game.
+fn level_up_menu<B: Console + Input>(
+    ui: &mut Ui<B>,
+    game: &Game,
+    world: &World,
+) -> Option<LevelUpChoice> {
+    loop {
+        render_all(ui, game, world);
+        let choice = menu(
+            "Level up! Choose a stat to raise:\n",
+            &[
+                format!(
+                    "Constitution (+{} HP, from {})",
+                    LEVEL_UP_HP,
+                    world.max_hp(world.player, game)
+                ),
+                format!(
+                    "Strength (+{} attack, from {})",
+                    LEVEL_UP_POWER,
+                    world.power(world.player, game)
+                ),
+                format!(
+                    "Agility (+{} defense, from {})",
+                    LEVEL_UP_DEFENSE,
+                    world.defense(world.player, game)
+                ),
+            ],
+            LEVEL_SCREEN_WIDTH,
+            &mut ui.backend,
+        );
+        match choice {
+            Some(0) => return Some(LevelUpChoice::Constitution),
+            Some(1) => return Some(LevelUpChoice::Strength),
+            Some(2) => return Some(LevelUpChoice::Agility),
+            // the choice waits for the next time the game is played
+            _ if ui.backend.is_closed() => return None,
+            _ => {}
+        }
+    }
+}
+
+/// Show a list of options in a window over the screen, and wait for the player
+/// to pick one with its letter. Any other key cancels.
+fn menu<T: AsRef<str>, B: Console + Input>(
+    header: &str,
+    options: &[T],
+    width: i32,
+    backend: &mut B,
+) -> Option<usize> {
+    assert!(
+        options.len() <= 26,
+        "Cannot have a menu with more than 26 options."
+    );
+
+    // calculate total height for the header (after auto-wrap) and one line per option
+    let header_height = if header.is_empty() {
+        0
+    } else {
+        wrap(header, width).len() as i32
+    };
+    let height = options.len() as i32 + header_height;
+
+    // the menu's window, centred, over a darkened background
+    let x = SCREEN_WIDTH / 2 - width / 2;
+    let y = SCREEN_HEIGHT / 2 - height / 2;
+    backend.blend_background((x, y), (width, height), BLACK, MENU_BACKGROUND_ALPHA);
+    for window_y in y..y + height {
+        for window_x in x..x + width {
+            backend.put_char(window_x, window_y, ' ', WHITE);
+        }
+    }
+
+    // print the header, with auto-wrap
+    if !header.is_empty() {
+        backend.print_rect(x, y, width, header, WHITE);
+    }
+
+    // print all the options, cut off at the edge of the window
+    for (index, option_text) in options.iter().enumerate() {
+        let menu_letter = (b'a' + index as u8) as char;
+        let text = format!("({}) {}", menu_letter, option_text.as_ref());
+        let text: String = text.chars().take(width as usize).collect();
+        backend.print(x, y + header_height + index as i32, &text, WHITE);
+    }
+
+    // present it to the player and wait for a key-press
+    backend.flush();
+    let key = backend.wait_for_keypress();
+
+    // convert the letter to an index; if it corresponds to an option, return it
+    match key {
+        Some(Keypress::Char(letter)) if letter.is_ascii_alphabetic() => {
+            let index = letter.to_ascii_lowercase() as usize - 'a' as usize;
+            if index < options.len() {
+                Some(index)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Show the player's inventory as a menu and return the index of the chosen item
+fn inventory_menu<B: Console + Input>(
+    inventory: &[Bundle],
+    header: &str,
+    backend: &mut B,
+) -> Option<usize> {
+    // show a menu with each item of the inventory as an option
+    let options = if inventory.is_empty() {
+        vec!["Inventory is empty.".into()]
+    } else {
+        inventory
+            .iter()
+            .map(|item| {
+                // show additional information, in case it's equipped
+                match item.equipment {
+                    Some(equipment) if equipment.equipped => {
+                        format!("{} (on {})", item.object.name, equipment.slot)
+                    }
+                    _ => item.object.name.clone(),
+                }
+            })
+            .collect()
+    };
+
+    let inventory_index = menu(header, &options, INVENTORY_WIDTH, backend);
+
+    // if an item was chosen, return it
+    if !inventory.is_empty() {
+        inventory_index
+    } else {
+        None
+    }
+}
+
+/// Let the player pick a target for an item, with the mouse or by moving a
+/// cursor with the arrow keys. The path to the cursor and the area the item
+/// would hit are highlighted. Returns `None` if the player cancelled.
+fn target_mode<B: Console + Input>(
+    ui: &mut Ui<B>,
+    game: &Game,
+    world: &World,
+    targeting: Targeting,
+) -> Option<Target> {
+    let (range, radius) = match targeting {
+        Targeting::None => return None,
+        Targeting::Tile { range, radius } => (range, radius),
+        Targeting::Monster { range } => (range, 0),
+    };
+    // start on the closest monster, if there is one in range
+    let player_pos = world.objects[world.player].pos();
+    let mut cursor =
+        closest_monster(range, game, world).map_or(player_pos, |id| world.objects[id].pos());
+
+    while !ui.backend.is_closed() {
+        render_all(ui, game, world);
+
+        // highlight the path to the cursor, the area around it and the cursor itself
+        let target = target_at(targeting, cursor, game, world);
+        for (x, y) in Line::new(player_pos, cursor) {
+            if game.fov.is_in_fov(x, y) {
+                highlight(ui, (x, y), COLOR_TARGET_PATH);
+            }
+        }
+        for y in (cursor.1 - radius)..=(cursor.1 + radius) {
+            for x in (cursor.0 - radius)..=(cursor.0 + radius) {
+                let in_radius = (x - cursor.0).pow(2) + (y - cursor.1).pow(2) <= radius.pow(2);
+                if in_radius && game.fov.is_in_fov(x, y) {
+                    highlight(ui, (x, y), COLOR_TARGET_AREA);
+                }
+            }
+        }
+        let cursor_color = if target.is_some() { WHITE } else { RED };
+        highlight(ui, cursor, cursor_color);
+        ui.backend.print(
+            1,
+            0,
+            "Choose a target: click or Enter to confirm, right-click or Escape to cancel.",
+            WHITE,
+        );
+        ui.backend.flush();
+
+        // move the cursor, or pick the tile under it
+        let mut confirm = false;
+        match ui.backend.check_for_event() {
+            Some(Event::Mouse(mouse)) => {
+                ui.mouse = mouse;
+                let mouse_pos = ui.camera.to_map((mouse.x, mouse.y));
+                if let Some((x, y)) = mouse_pos {
+                    if x < MAP_WIDTH && y < MAP_HEIGHT {
+                        cursor = (x, y);
+                    }
+                }
+                if mouse.right {
+                    return None;
+                }
+                confirm = mouse.left;
+            }
+            Some(Event::Key(key)) => {
+                let (dx, dy) = match key {
+                    Keypress::Enter => {
+                        confirm = true;
+                        (0, 0)
+                    }
+                    Keypress::Escape => return None,
+                    // the cursor moves with whatever keys move the player
+                    _ => match ui.keys.command(key) {
+                        Some(Command::Move(dx, dy)) => (dx, dy),
+                        _ => (0, 0),
+                    },
+                };
+                cursor = (
+                    (cursor.0 + dx).clamp(0, MAP_WIDTH - 1),
+                    (cursor.1 + dy).clamp(0, MAP_HEIGHT - 1),
+                );
+                // and don't let it wander off the screen
+                let camera = ui.camera;
+                cursor = (
+                    cursor.0.clamp(camera.x, camera.x + camera.width - 1),
+                    cursor.1.clamp(camera.y, camera.y + camera.height - 1),
+                );
+            }
+            None => {}
+        }
+        if confirm && target.is_some() {
+            return target;
+        }
+    }
+    None
+}
+
+/// set the background of the map position `pos`, if it is on screen
+fn highlight<B: Console>(ui: &mut Ui<B>, pos: (i32, i32), color: Color) {
+    if let Some((x, y)) = ui.camera.to_screen(pos) {
+        ui.backend.set_background(x, y, color);
+    }
+}
+
+/// A menu with no options, to show a message until any key is pressed
+fn msgbox<B: Console + Input>(text: &str, width: i32, backend: &mut B) {
+    let options: &[&str] = &[];
+    menu(text, options, width, backend);
+}
+
+/// read `--seed <number>` from the command line arguments
+fn parse_seed(mut args: impl Iterator<Item = String>) -> Result<Option<u64>, String> {
+    let mut seed = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => {
+                let value = args.next().ok_or("--seed needs a number after it")?;
+                let value = value
+                    .parse()
+                    .map_err(|_| format!("invalid seed '{}', expected a number", value))?;
+                seed = Some(value);
+            }
+            _ => return Err(format!("unknown argument '{}'", arg)),
+        }
+    }
+    Ok(seed)
+}
+
+/// draw the character that represents this object at its position on the
+/// screen, in its color
+fn draw_object(object: &Object, camera: &Camera, con: &mut impl Console) {
+    if let Some((x, y)) = camera.to_screen(object.pos()) {
+        con.put_char(x, y, object.char, object.color);
+    }
+}
+
+/// render all of the things
+pub fn render_all<B: Console>(ui: &mut Ui<B>, game: &Game, world: &World) {
+    ui.backend.clear();
+
+    // keep the player in view
+    ui.camera
+        .follow(world.objects[world.player].pos(), (MAP_WIDTH, MAP_HEIGHT));
+
+    // Draw the tiles that are on screen
+    for screen_y in 0..VIEW_HEIGHT {
+        for screen_x in 0..VIEW_WIDTH {
+            let (x, y) = match ui.camera.to_map((screen_x, screen_y)) {
+                Some((x, y)) if x < MAP_WIDTH && y < MAP_HEIGHT => (x, y),
+                _ => continue,
+            };
+            let visible = game.fov.is_in_fov(x, y);
+            let tile = &game.map[x as usize][y as usize];
+            if tile.explored {
+                let (foreground, background) = tile.kind.colors(visible);
+                ui.backend.put_char_ex(
+                    screen_x,
+                    screen_y,
+                    tile.kind.glyph(),
+                    foreground,
+                    background,
+                );
+            }
+        }
+    }
+
+    // get the list of objects within the FOV to draw
+    let mut to_draw: Vec<_> = world
+        .objects
+        .values()
+        .filter(|o| {
+            game.fov.is_in_fov(o.x, o.y)
+                || (o.always_visible && game.map[o.x as usize][o.y as usize].explored)
+        })
+        .collect();
+    // sort so that non-blocking objects come first
+    to_draw.sort_by_key(|o| o.blocks);
+    // Draw all of our objects on the screen
+    for object in &to_draw {
+        draw_object(object, &ui.camera, &mut ui.backend);
+    }
+
+    // the GUI panel, below the map
+    let panel = &mut ui.backend;
+
+    // display names of objects under the mouse, just above the panel
+    let names = ui
+        .camera
+        .to_map((ui.mouse.x, ui.mouse.y))
+        .map_or(String::new(), |(x, y)| get_names_under(x, y, game, world));
+    panel.print(1, NAMES_Y, &names, LIGHT_GREY);
+
+    // show the player's stats
+    if let Some(fighter) = world.fighters.get(world.player) {
+        panel.print(
+            1,
+            PANEL_Y + 1,
+            &format!("HP: {}/{} ", fighter.hp, world.max_hp(world.player, game)),
+            WHITE,
+        );
+    }
+    let player = &world.objects[world.player];
+    panel.print(
+        1,
+        PANEL_Y + 2,
+        &format!(
+            "Level: {}  XP: {}/{}",
+            player.level,
+            world.fighters.get(world.player).map_or(0, |f| f.xp),
+            level_up_xp(player.level)
+        ),
+        WHITE,
+    );
+    panel.print(
+        1,
+        PANEL_Y + 3,
+        &format!("Dungeon level: {}  Turn: {}", game.dungeon_level, game.turn),
+        WHITE,
+    );
+    panel.print(1, PANEL_Y + 4, &format!("Seed: {}", game.seed), WHITE);
+
+    // and whatever the player is under, with how long it has left
+    let mut x = 1;
+    for effect in world.effects(world.player) {
+        let text = format!("{} {}", effect.kind, effect.turns);
+        if x + text.len() as i32 > BAR_WIDTH {
+            break;
+        }
+        panel.print(x, PANEL_Y + 5, &text, effect.kind.color());
+        x += text.len() as i32 + 1;
+    }
+
+    // print the game messages, newest at the bottom, one line at a time
+    let mut y = MSG_HEIGHT + 1;
+    for message in game.messages.iter().rev() {
+        let msg_height = wrap(&message.text, MSG_WIDTH).len() as i32;
+        y -= msg_height;
+        if y < 1 {
+            break;
+        }
+        panel.print_rect(MSG_X, PANEL_Y + y, MSG_WIDTH, &message.text, message.color);
+    }
+}
+
+/// Show the whole message log full screen, until the player closes it with
+/// Escape or whichever key opened it. The arrow keys and page up/down scroll
+/// through older messages.
+fn show_message_history<B: Console + Input>(
+    backend: &mut B,
+    messages: &Messages,
+    keys: &KeyBindings,
+) {
+    // the index of the message at the top of the screen, starting at the end
+    let page = (SCREEN_HEIGHT - 2) as usize;
+    let mut top = messages.len().saturating_sub(page);
+    loop {
+        backend.clear();
+        backend.print_centered(
+            SCREEN_WIDTH / 2,
+            0,
+            "Message history (arrows/page up/page down to scroll, escape to close)",
+            WHITE,
+        );
+
+        let mut y = 1;
+        for message in messages.iter().skip(top) {
+            let text = format!("[{}] {}", message.turn, message.text);
+            let height = wrap(&text, SCREEN_WIDTH).len() as i32;
+            if y + height > SCREEN_HEIGHT {
+                break;
+            }
+            backend.print_rect(0, y, SCREEN_WIDTH, &text, message.color);
+            y += height;
+        }
+        backend.flush();
+
+        let last_top = messages.len().saturating_sub(1);
+        match backend.wait_for_keypress() {
+            Some(Keypress::Up) => top = top.saturating_sub(1),
+            Some(Keypress::Down) => top = (top + 1).min(last_top),
+            Some(Keypress::PageUp) => top = top.saturating_sub(page),
+            Some(Keypress::PageDown) => top = (top + page).min(last_top),
+            Some(Keypress::Escape) | None => break,
+            Some(key) if keys.command(key) == Some(Command::MessageLog) => break,
+            _ => {}
+        }
+    }
+}
+
+/// Handle Key inputs from the user
+fn handle_keys<B: Console + Input>(
+    ui: &mut Ui<B>,
+    game: &mut Game,
+    world: &mut World,
+) -> PlayerAction {
+    use PlayerAction::*;
+
+    // everything goes through the key bindings
+    let command = match ui.key.and_then(|key| ui.keys.command(key)) {
+        Some(command) => command,
+        None => return DidntTakeTurn,
+    };
+    let player_alive = world.objects[world.player].alive;
+    match (command, player_alive) {
+        // Exit the game
+        (Command::Exit, _) => Exit,
+        // show the full message history
+        (Command::MessageLog, _) => {
+            show_message_history(&mut ui.backend, &game.messages, &ui.keys);
+            DidntTakeTurn
+        }
+        // movement keys
+        (Command::Move(dx, dy), true) => {
+            player_move_or_attack(dx, dy, game, world);
+            TookTurn
+        }
+        // do nothing, and let the monsters come
+        (Command::Wait, true) => {
+            wait(world.player, world);
+            TookTurn
+        }
+        // look for secret doors
+        (Command::Search, true) => {
+            search(game, world);
+            TookTurn
+        }
+        // pick up an item
+        (Command::PickUp, true) => match item_under_player(world) {
+            Some(item_id) if pick_item_up(item_id, game, world) => TookTurn,
+            _ => DidntTakeTurn,
+        },
+        // go down the stairs, if the player is on them
+        (Command::Descend, true) => {
+            if take_stairs(game, world) {
+                TookTurn
+            } else {
+                DidntTakeTurn
+            }
+        }
+        // show the inventory: use or drop an item
+        (Command::Inventory, true) => {
+            let header = "Press the key next to an item to choose it, or any other to cancel.\n";
+            let chosen = inventory_menu(&game.inventory, header, &mut ui.backend);
+            let action = chosen.and_then(|inventory_index| {
+                let header = format!(
+                    "What do you want to do with the {}?\n",
+                    game.inventory[inventory_index].object.name
+                );
+                menu(&header, &["Use", "Drop"], INVENTORY_WIDTH, &mut ui.backend)
+                    .map(|action| (inventory_index, action))
+            });
+            match action {
+                Some((inventory_index, 0)) => {
+                    // point the item at something first, if it needs it
+                    let targeting = game.inventory[inventory_index]
+                        .item
+                        .map_or(Targeting::None, |item| item.targeting());
+                    let target = match targeting {
+                        Targeting::None => None,
+                        _ => match target_mode(ui, game, world, targeting) {
+                            Some(target) => Some(target),
+                            None => return DidntTakeTurn,
+                        },
+                    };
+                    match use_item(inventory_index, target, game, world) {
+                        UseResult::UsedUp | UseResult::UsedAndKept => TookTurn,
+                        UseResult::Cancelled => DidntTakeTurn,
+                    }
+                }
+                Some((inventory_index, 1)) => {
+                    drop_item(inventory_index, game, world);
+                    TookTurn
+                }
+                _ => DidntTakeTurn,
+            }
+        }
+        _ => DidntTakeTurn,
+    }
+}
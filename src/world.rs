@@ -0,0 +1,476 @@
+use serde::{Deserialize, Serialize};
+use std::ops::{Index, IndexMut};
+
+use crate::object::{Ai, Equipment, Fighter, Item, Object};
+
+/*********  STRUCTURES  *********/
+/// A handle to an entity in the world. Its slot may be reused once the entity
+/// is despawned, but the generation changes when it is, so an old handle
+/// finds nothing rather than whatever took its place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// One kind of component, kept apart from the others, for whichever entities
+/// have it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Storage<T> {
+    /// one slot per entity index, with the generation of the entity the
+    /// component belongs to; empty for those without it
+    slots: Vec<Option<(u32, T)>>,
+}
+
+/// Every entity on the current level. All of them have an `Object`, with where
+/// they are and how they look; everything else is a component only some have,
+/// stored separately so a system can go through just the entities it cares
+/// about.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct World {
+    /// the current generation of each slot, bumped whenever its entity is
+    /// despawned
+    generations: Vec<u32>,
+    /// slots with no entity in them, to be reused first
+    free: Vec<u32>,
+    pub player: Entity,
+    pub objects: Storage<Object>,
+    pub fighters: Storage<Fighter>,
+    pub ais: Storage<Ai>,
+    /// for monsters, where they last saw the player, so they can go looking
+    pub last_seen_player: Storage<(i32, i32)>,
+    /// for monsters that hunt together, the first of their pack to be
+    /// spawned; the same for every member, even once that one is gone
+    pub packs: Storage<Entity>,
+    pub items: Storage<Item>,
+    pub equipment: Storage<Equipment>,
+}
+
+/// An entity with all its components, outside of any world: one being built
+/// to spawn, or one taken out to be kept elsewhere, like the inventory
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    pub object: Object,
+    pub fighter: Option<Fighter>,
+    pub ai: Option<Ai>,
+    pub item: Option<Item>,
+    pub equipment: Option<Equipment>,
+}
+
+impl Entity {
+    /// where its components are kept; only unique among living entities
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+}
+
+impl<T> Storage<T> {
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index()) {
+            Some(Some((generation, component))) if *generation == entity.generation => {
+                Some(component)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index()) {
+            Some(Some((generation, component))) if *generation == entity.generation => {
+                Some(component)
+            }
+            _ => None,
+        }
+    }
+
+    /// Both entities' components at once, to change one based on the other.
+    /// `None` if either doesn't have one, or they are the same entity.
+    pub fn get_two_mut(&mut self, a: Entity, b: Entity) -> Option<(&mut T, &mut T)> {
+        if a.index == b.index || !self.has(a) || !self.has(b) {
+            return None;
+        }
+        // split the slots between the two, so each half can be borrowed
+        let (low, high) = (a.index().min(b.index()), a.index().max(b.index()));
+        let (left, right) = self.slots.split_at_mut(high);
+        let low = left[low].as_mut().map(|(_, component)| component)?;
+        let high = right[0].as_mut().map(|(_, component)| component)?;
+        if a.index() < b.index() {
+            Some((low, high))
+        } else {
+            Some((high, low))
+        }
+    }
+
+    pub fn has(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    /// Give an entity the component, replacing any it had. Returns false,
+    /// changing nothing, if another entity in the same slot has one. A storage
+    /// can't tell whether the entity itself is still alive; `World::insert`
+    /// can.
+    pub fn insert(&mut self, entity: Entity, component: T) -> bool {
+        if self.slots.len() <= entity.index() {
+            self.slots.resize_with(entity.index() + 1, || None);
+        }
+        let slot = &mut self.slots[entity.index()];
+        if let Some((generation, _)) = slot {
+            if *generation != entity.generation {
+                return false;
+            }
+        }
+        *slot = Some((entity.generation, component));
+        true
+    }
+
+    /// take the component away from an entity, returning it
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        if !self.has(entity) {
+            return None;
+        }
+        self.slots[entity.index()]
+            .take()
+            .map(|(_, component)| component)
+    }
+
+    /// the entities with this component, along with it
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref().map(|(generation, component)| {
+                let entity = Entity {
+                    index: index as u32,
+                    generation: *generation,
+                };
+                (entity, component)
+            })
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut().map(|(generation, component)| {
+                    let entity = Entity {
+                        index: index as u32,
+                        generation: *generation,
+                    };
+                    (entity, component)
+                })
+            })
+    }
+
+    /// just the entities that have it
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.iter().map(|(entity, _)| entity)
+    }
+
+    /// just the components
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.iter().map(|(_, component)| component)
+    }
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Storage { slots: vec![] }
+    }
+}
+
+/// `storage[entity]` panics if the entity doesn't have the component; use
+/// `get` when it might not
+impl<T> Index<Entity> for Storage<T> {
+    type Output = T;
+
+    fn index(&self, entity: Entity) -> &T {
+        self.get(entity)
+            .expect("entity is gone or doesn't have this component")
+    }
+}
+
+impl<T> IndexMut<Entity> for Storage<T> {
+    fn index_mut(&mut self, entity: Entity) -> &mut T {
+        self.get_mut(entity)
+            .expect("entity is gone or doesn't have this component")
+    }
+}
+
+impl World {
+    /// a world with nobody in it but the player
+    pub fn new(player: Bundle) -> Self {
+        let mut world = World {
+            generations: vec![],
+            free: vec![],
+            player: Entity {
+                index: 0,
+                generation: 0,
+            },
+            objects: Storage::default(),
+            fighters: Storage::default(),
+            ais: Storage::default(),
+            last_seen_player: Storage::default(),
+            packs: Storage::default(),
+            items: Storage::default(),
+            equipment: Storage::default(),
+        };
+        world.player = world.spawn(player);
+        world
+    }
+
+    /// Put an entity into the world, returning it. It may reuse the slot of
+    /// one despawned earlier, but never its handle.
+    pub fn spawn(&mut self, bundle: Bundle) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                (self.generations.len() - 1) as u32
+            }
+        };
+        let entity = Entity {
+            index,
+            generation: self.generations[index as usize],
+        };
+        self.objects.insert(entity, bundle.object);
+        if let Some(fighter) = bundle.fighter {
+            self.fighters.insert(entity, fighter);
+        }
+        if let Some(ai) = bundle.ai {
+            self.ais.insert(entity, ai);
+        }
+        if let Some(item) = bundle.item {
+            self.items.insert(entity, item);
+        }
+        if let Some(equipment) = bundle.equipment {
+            self.equipment.insert(entity, equipment);
+        }
+        entity
+    }
+
+    /// Give an entity a component, in whichever storage `storage` picks out,
+    /// e.g. `world.insert(id, |w| &mut w.ais, Ai::Basic)`. Returns false,
+    /// changing nothing, if the entity is no longer in the world.
+    pub fn insert<T>(
+        &mut self,
+        entity: Entity,
+        storage: fn(&mut World) -> &mut Storage<T>,
+        component: T,
+    ) -> bool {
+        self.contains(entity) && storage(self).insert(entity, component)
+    }
+
+    /// Take an entity out of the world, with its components. Every handle to
+    /// it expires; no other entity changes.
+    pub fn despawn(&mut self, entity: Entity) -> Option<Bundle> {
+        let object = self.objects.remove(entity)?;
+        self.last_seen_player.remove(entity);
+        self.packs.remove(entity);
+        let bundle = Bundle {
+            object,
+            fighter: self.fighters.remove(entity),
+            ai: self.ais.remove(entity),
+            item: self.items.remove(entity),
+            equipment: self.equipment.remove(entity),
+        };
+        self.generations[entity.index()] += 1;
+        self.free.push(entity.index);
+        Some(bundle)
+    }
+
+    /// despawn every entity but those `keep` is true for
+    pub fn retain(&mut self, keep: impl Fn(Entity) -> bool) {
+        let gone: Vec<_> = self.entities().filter(|&e| !keep(e)).collect();
+        for entity in gone {
+            self.despawn(entity);
+        }
+    }
+
+    /// whether the entity is still in the world
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.objects.has(entity)
+    }
+
+    /// every entity in the world
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.objects.entities()
+    }
+
+    /// how many entities there are
+    pub fn len(&self) -> usize {
+        self.objects.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether every component belongs to an entity in the world, each slot is
+    /// either used or free, and the player is there, as it should be unless a
+    /// save file has been tampered with.
+    pub fn is_consistent(&self) -> bool {
+        let is_current =
+            |entity: Entity| self.generations.get(entity.index()) == Some(&entity.generation);
+        let free_are_empty = self.free.iter().all(|&index| {
+            (index as usize) < self.generations.len()
+                && self
+                    .objects
+                    .slots
+                    .get(index as usize)
+                    .is_none_or(Option::is_none)
+        });
+        let used = self.len() + self.free.len() == self.generations.len();
+        self.objects.entities().all(is_current)
+            && self.all_alive(&self.fighters)
+            && self.all_alive(&self.ais)
+            && self.all_alive(&self.last_seen_player)
+            && self.all_alive(&self.packs)
+            && self.all_alive(&self.items)
+            && self.all_alive(&self.equipment)
+            && free_are_empty
+            && used
+            && self.contains(self.player)
+    }
+
+    /// whether everything in `storage` belongs to an entity in the world
+    fn all_alive<T>(&self, storage: &Storage<T>) -> bool {
+        storage.entities().all(|entity| self.contains(entity))
+    }
+}
+
+impl Bundle {
+    /// an entity with no components but its object
+    pub fn new(object: Object) -> Self {
+        Bundle {
+            object,
+            fighter: None,
+            ai: None,
+            item: None,
+            equipment: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colors::WHITE;
+    use crate::object::Ai;
+
+    fn bundle(name: &str) -> Bundle {
+        Bundle::new(Object::new(0, 0, '?', WHITE, false, name))
+    }
+
+    fn world() -> World {
+        World::new(bundle("player"))
+    }
+
+    #[test]
+    fn despawned_handles_find_nothing() {
+        let mut world = world();
+        let orc = world.spawn(bundle("orc"));
+        world.ais.insert(orc, Ai::Basic);
+        assert!(world.despawn(orc).is_some());
+
+        assert!(!world.contains(orc));
+        assert!(world.objects.get(orc).is_none());
+        assert!(world.ais.get(orc).is_none());
+        assert!(world.despawn(orc).is_none());
+        assert!(world.is_consistent());
+    }
+
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let mut world = world();
+        let orc = world.spawn(bundle("orc"));
+        world.despawn(orc);
+        let troll = world.spawn(bundle("troll"));
+
+        assert_eq!(troll.index(), orc.index());
+        assert_ne!(troll, orc);
+        assert!(world.objects.get(orc).is_none());
+        assert_eq!(world.objects[troll].name, "troll");
+        // and the old handle can't take the new entity's components either
+        assert!(world.objects.remove(orc).is_none());
+        assert!(world.despawn(orc).is_none());
+        assert!(world.contains(troll));
+    }
+
+    #[test]
+    fn stale_handles_cant_insert_into_a_reused_slot() {
+        let mut world = world();
+        let orc = world.spawn(bundle("orc"));
+        world.despawn(orc);
+        let troll = world.spawn(bundle("troll"));
+        assert_eq!(troll.index(), orc.index());
+
+        // the troll has no AI yet: the slot is empty, but the orc is still gone
+        assert!(!world.insert(orc, |w| &mut w.ais, Ai::Basic));
+        assert!(world.ais.get(troll).is_none());
+        assert!(world.insert(troll, |w| &mut w.ais, Ai::Basic));
+
+        // and a component the troll does have isn't overwritten, even by the
+        // storage on its own
+        let confused = Ai::Confused {
+            previous_ai: Box::new(Ai::Basic),
+            turns: 3,
+        };
+        assert!(!world.insert(orc, |w| &mut w.ais, confused.clone()));
+        assert!(!world.ais.insert(orc, confused));
+        assert_eq!(world.ais.get(troll), Some(&Ai::Basic));
+        assert!(world.ais.get(orc).is_none());
+        assert!(world.is_consistent());
+    }
+
+    #[test]
+    fn get_two_mut_needs_two_different_entities() {
+        let mut world = world();
+        let player = world.player;
+        let orc = world.spawn(bundle("orc"));
+        let gone = world.spawn(bundle("gone"));
+        world.despawn(gone);
+
+        assert!(world.objects.get_two_mut(orc, orc).is_none());
+        assert!(world.objects.get_two_mut(orc, gone).is_none());
+        let (a, b) = world.objects.get_two_mut(orc, player).unwrap();
+        assert_eq!((a.name.as_str(), b.name.as_str()), ("orc", "player"));
+        let (a, b) = world.objects.get_two_mut(player, orc).unwrap();
+        assert_eq!((a.name.as_str(), b.name.as_str()), ("player", "orc"));
+    }
+
+    #[test]
+    fn components_of_dead_entities_are_inconsistent() {
+        let mut world = world();
+        let orc = world.spawn(bundle("orc"));
+        world.despawn(orc);
+        assert!(world.is_consistent());
+
+        world.ais.insert(orc, Ai::Basic);
+        assert!(!world.is_consistent());
+        world.ais.remove(orc);
+        assert!(world.is_consistent());
+
+        // a component in a slot nothing has been spawned in yet
+        let never = Entity {
+            index: 10,
+            generation: 0,
+        };
+        world.last_seen_player.insert(never, (1, 1));
+        assert!(!world.is_consistent());
+    }
+
+    #[test]
+    fn retain_only_despawns_the_rest() {
+        let mut world = world();
+        let player = world.player;
+        let orc = world.spawn(bundle("orc"));
+        let troll = world.spawn(bundle("troll"));
+        world.retain(|entity| entity != orc);
+
+        assert!(world.contains(player) && world.contains(troll));
+        assert!(!world.contains(orc));
+        assert_eq!(world.len(), 2);
+        assert!(world.is_consistent());
+    }
+}
@@ -0,0 +1,196 @@
+//! Snapshot tests of what `render_all` draws, on an in-memory console.
+//!
+//! Each snapshot in `tests/snapshots` is a frame's glyphs, then its foreground
+//! and background colors. After a change that is meant to alter them, run
+//! `UPDATE_SNAPSHOTS=1 cargo test --no-default-features --test render`, and
+//! check the diff before committing it.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use roguelike::buffer::Buffer;
+use roguelike::camera::Camera;
+use roguelike::colors::*;
+use roguelike::game::{new_game, Game};
+use roguelike::keys::KeyBindings;
+use roguelike::object::{Item, Object};
+use roguelike::spawns::SpawnTable;
+use roguelike::ui::{render_all, Ui, SCREEN_HEIGHT, SCREEN_WIDTH};
+use roguelike::world::{Bundle, World};
+
+fn new_ui() -> Ui<Buffer> {
+    Ui::new(
+        Buffer::new(SCREEN_WIDTH, SCREEN_HEIGHT),
+        KeyBindings::default(),
+    )
+}
+
+fn render(game: &Game, world: &World) -> Buffer {
+    render_with_camera(game, world).0
+}
+
+/// a frame, and the part of the map it shows
+fn render_with_camera(game: &Game, world: &World) -> (Buffer, Camera) {
+    let mut ui = new_ui();
+    render_all(&mut ui, game, world);
+    let camera = ui.camera();
+    (ui.backend, camera)
+}
+
+/// Compare a frame with the snapshot called `name`, or write it there if
+/// `UPDATE_SNAPSHOTS` is set
+fn assert_snapshot(name: &str, frame: &Buffer) {
+    let actual = format!(
+        "{}\n{}\n{}",
+        frame.to_text(),
+        frame.foregrounds_text(),
+        frame.backgrounds_text()
+    );
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "snapshots", name]
+        .iter()
+        .collect();
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no snapshot at {}; run with UPDATE_SNAPSHOTS=1 to write it",
+            path.display()
+        )
+    });
+    let changed: Vec<_> = expected
+        .lines()
+        .zip(actual.lines())
+        .enumerate()
+        .filter(|(_, (expected, actual))| expected != actual)
+        .map(|(line, (expected, actual))| {
+            format!("line {}:\n  - {}\n  + {}", line + 1, expected, actual)
+        })
+        .collect();
+    assert!(
+        changed.is_empty() && expected.lines().count() == actual.lines().count(),
+        "{} doesn't match the frame:\n{}",
+        name,
+        changed.join("\n")
+    );
+}
+
+/// every position on screen that shows the map
+fn screen_positions(camera: Camera) -> impl Iterator<Item = (i32, i32)> {
+    (0..camera.height).flat_map(move |y| (0..camera.width).map(move |x| (x, y)))
+}
+
+/// the row of the frame that starts with `prefix`, once leading spaces are skipped
+fn line_starting_with(frame: &Buffer, prefix: &str) -> Option<String> {
+    frame
+        .to_text()
+        .lines()
+        .find(|line| line.trim_start().starts_with(prefix))
+        .map(str::to_string)
+}
+
+#[test]
+fn first_frame_matches_snapshot() {
+    for seed in [1, 42] {
+        let (game, world) = new_game(seed, SpawnTable::default());
+        assert_snapshot(&format!("seed_{}.txt", seed), &render(&game, &world));
+    }
+}
+
+#[test]
+fn tiles_are_lit_in_view_and_dark_out_of_it() {
+    let (mut game, world) = new_game(1, SpawnTable::default());
+    // remember the whole map, so everything out of view is drawn dark
+    for column in game.map.iter_mut() {
+        for tile in column.iter_mut() {
+            tile.explored = true;
+        }
+    }
+    let (frame, camera) = render_with_camera(&game, &world);
+
+    let (mut lit_walls, mut dark_walls, mut lit_ground, mut dark_ground) = (0, 0, 0, 0);
+    for (screen_x, screen_y) in screen_positions(camera) {
+        let (x, y) = camera.to_map((screen_x, screen_y)).unwrap();
+        let tile = &game.map[x as usize][y as usize];
+        let visible = game.fov.is_in_fov(x, y);
+        let (_, background) = tile.kind.colors(visible);
+        let cell = frame.cell(screen_x, screen_y).unwrap();
+        assert_eq!(cell.background, background, "the tile at ({}, {})", x, y);
+        match (tile.blocked(), visible) {
+            (true, true) => lit_walls += 1,
+            (true, false) => dark_walls += 1,
+            (false, true) => lit_ground += 1,
+            (false, false) => dark_ground += 1,
+        }
+    }
+    assert!(lit_walls > 0 && dark_walls > 0 && lit_ground > 0 && dark_ground > 0);
+}
+
+#[test]
+fn unexplored_tiles_are_black() {
+    let (game, world) = new_game(42, SpawnTable::default());
+    let (frame, camera) = render_with_camera(&game, &world);
+    let (screen_x, screen_y) = screen_positions(camera)
+        .find(|&pos| {
+            let (x, y) = camera.to_map(pos).unwrap();
+            !game.map[x as usize][y as usize].explored
+        })
+        .unwrap();
+    let cell = frame.cell(screen_x, screen_y).unwrap();
+    assert_eq!((cell.glyph, cell.background), (' ', BLACK));
+}
+
+#[test]
+fn blocking_objects_are_drawn_over_the_rest() {
+    let (game, mut world) = new_game(1, SpawnTable::default());
+    let (x, y) = world.objects[world.player].pos();
+    // an item, spawned after the player, under their feet
+    let mut potion = Bundle::new(Object::new(x, y, '!', VIOLET, false, "healing potion"));
+    potion.item = Some(Item::Heal);
+    world.spawn(potion);
+
+    let (frame, camera) = render_with_camera(&game, &world);
+    let (screen_x, screen_y) = camera.to_screen((x, y)).unwrap();
+    let cell = frame.cell(screen_x, screen_y).unwrap();
+    assert_eq!((cell.glyph, cell.foreground), ('@', WHITE));
+}
+
+#[test]
+fn hp_line_shows_damage() {
+    let (mut game, mut world) = new_game(1, SpawnTable::default());
+    let before = render(&game, &world);
+    assert_eq!(
+        line_starting_with(&before, "HP:").unwrap().trim(),
+        "HP: 30/30"
+    );
+
+    let player = world.player;
+    world.take_damage(player, 5, &mut game);
+    let after = render(&game, &world);
+    let hp_line = line_starting_with(&after, "HP:").unwrap();
+    assert_eq!(hp_line.trim(), "HP: 25/30");
+
+    // and nothing else on screen changed
+    let changes = before.diff(&after);
+    let row = after
+        .to_text()
+        .lines()
+        .position(|line| line == hp_line)
+        .unwrap() as i32;
+    assert!(!changes.is_empty());
+    for change in &changes {
+        assert_eq!(change.y, row, "{}", change);
+    }
+}
+
+#[test]
+fn rendering_twice_draws_the_same_frame() {
+    let (game, world) = new_game(42, SpawnTable::default());
+    let mut ui = new_ui();
+    render_all(&mut ui, &game, &world);
+    let first = ui.backend.clone();
+    render_all(&mut ui, &game, &world);
+    assert!(first.diff(&ui.backend).is_empty());
+}